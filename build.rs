@@ -1,28 +1,89 @@
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufWriter;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 
-use vst3_bindgen::generate;
+use vst3_bindgen::generate_with_extensions;
 
-fn main() {
-    println!("cargo:rerun-if-env-changed=VST3_SDK_DIR");
-    let vst3_sdk_dir = if let Ok(dir) = env::var("VST3_SDK_DIR") {
+fn find_presonus_headers() -> Vec<PathBuf> {
+    println!("cargo:rerun-if-env-changed=PRESONUS_SDK_DIR");
+    let presonus_sdk_dir = if let Ok(dir) = env::var("PRESONUS_SDK_DIR") {
         dir
     } else {
-        eprintln!("please provide a value for VST3_SDK_DIR");
+        eprintln!("the presonus feature requires a value for PRESONUS_SDK_DIR");
         process::exit(1);
     };
 
-    println!("cargo:rerun-if-changed={}", vst3_sdk_dir);
+    println!("cargo:rerun-if-changed={}", presonus_sdk_dir);
+
+    let entries = match fs::read_dir(&presonus_sdk_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!(
+                "couldn't read PRESONUS_SDK_DIR ({}): {}",
+                presonus_sdk_dir, err
+            );
+            process::exit(1);
+        }
+    };
+
+    // Only the ipsl*.h headers declare VST 3 interfaces; the remaining headers in the PreSonus
+    // extensions package are for other plugin formats.
+    let mut headers = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            if name.starts_with("ipsl") && name.ends_with(".h") {
+                headers.push(path);
+            }
+        }
+    }
+    headers.sort();
+
+    headers
+}
+
+// Locates the VST 3 SDK. VST3_SDK_DIR takes precedence (it can also be set from a
+// .cargo/config.toml file); otherwise, a copy of the SDK vendored into the vst3sdk/ directory of
+// this crate (e.g. as a git submodule in a checkout of vst3-rs) is used if present.
+fn find_vst3_sdk() -> PathBuf {
+    println!("cargo:rerun-if-env-changed=VST3_SDK_DIR");
+    if let Some(dir) = env::var_os("VST3_SDK_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    let vendored = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("vst3sdk");
+    println!("cargo:rerun-if-changed={}", vendored.display());
+    if vendored.is_dir() {
+        return vendored;
+    }
+
+    eprintln!(
+        "couldn't find the VST 3 SDK. Set VST3_SDK_DIR to the path of the SDK directory (the one \
+         containing pluginterfaces/), either in the environment or in the [env] section of a \
+         .cargo/config.toml file. The SDK can be obtained with:\n\n    \
+         git clone --recursive https://github.com/steinbergmedia/vst3sdk.git"
+    );
+    process::exit(1);
+}
+
+fn main() {
+    let vst3_sdk_dir = find_vst3_sdk();
+
+    println!("cargo:rerun-if-changed={}", vst3_sdk_dir.display());
+
+    let mut extension_headers = Vec::new();
+    if env::var_os("CARGO_FEATURE_PRESONUS").is_some() {
+        extension_headers.extend(find_presonus_headers());
+    }
 
     let out_dir = env::var("OUT_DIR").unwrap();
 
     let bindings = File::create(Path::new(&out_dir).join("bindings.rs")).unwrap();
     let sink = BufWriter::new(bindings);
 
-    if let Err(err) = generate(Path::new(&vst3_sdk_dir), sink) {
+    if let Err(err) = generate_with_extensions(&vst3_sdk_dir, &extension_headers, sink) {
         eprintln!("{}", err);
         process::exit(1);
     }
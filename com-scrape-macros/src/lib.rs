@@ -0,0 +1,351 @@
+//! Procedural macros for defining COM interfaces compatible with `com-scrape-types`.
+//!
+//! This crate is re-exported by `com-scrape-types` and does not need to be depended on directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Error, Expr, FnArg, Ident, ItemTrait, Pat, Path, ReturnType, TraitItem, Type,
+};
+
+/// Defines a COM interface from a Rust trait definition.
+///
+/// `com_interface` generates the same set of definitions for an interface that `com-scrape`
+/// generates from a C++ header, which makes it possible to define new interfaces (e.g. private
+/// extension interfaces shared between a plugin and a host) without writing any C++:
+///
+/// ```ignore
+/// #[com_interface(iid = *b"0123456789abcdef", bases(IPluginBase, FUnknown))]
+/// pub trait IExtension: IPluginBaseTrait {
+///     unsafe fn extension_method(&self, value: i32) -> i32;
+/// }
+/// ```
+///
+/// Given a trait named `IExtension`, the macro generates:
+///
+/// 1. An interface type `IExtension` and a virtual table type `IExtensionVtbl`, whose first field
+///    is the virtual table of the first base interface, followed by one entry per method.
+///
+/// 2. Implementations of `Unknown` (which delegate to the first base interface), `Interface`, and
+///    `Inherits` for each base interface, along with a compile-time check that the first base
+///    interface implements `Inherits` for each of the others.
+///
+/// 3. A trait `IExtensionTrait` containing the methods of the interface, along with a blanket
+///    implementation of that trait for smart pointers to the interface. The supertraits of the
+///    input trait are retained; since the virtual table of the base interface is obtained from its
+///    `make_vtbl` function, they must include the traits for any base interfaces which have them.
+///
+/// 4. A `make_vtbl` associated function and an implementation of `Construct`, which make it
+///    possible to implement the interface from Rust.
+///
+/// The following arguments are accepted:
+///
+/// - `iid = <expr>` (required): A constant expression of type `Guid` giving the interface's IID.
+///
+/// - `bases(...)` (required): The base interfaces of the interface, starting with its immediate
+///   base and ending with the root interface (e.g. `IUnknown` or `FUnknown`). Each base interface
+///   must provide a `make_vtbl` function, as those generated by `com-scrape` and by this macro do.
+///   The virtual table and `Interface::inherits` only chain through the first base, so every other
+///   base must be one of its transitive bases; listing an unrelated interface is a compile error.
+///
+/// - `crate = <path>`: The path to the `com_scrape_types` crate. Defaults to
+///   `::com_scrape_types`, which is appropriate unless the crate is only available via a
+///   re-export (e.g. `vst3::com_scrape_types`).
+///
+/// Methods must be `unsafe`, must take `&self`, and use the `"system"` calling convention in the
+/// generated virtual table.
+#[proc_macro_attribute]
+pub fn com_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = Args::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("iid") {
+            args.iid = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("bases") {
+            meta.parse_nested_meta(|meta| {
+                args.bases.push(meta.path);
+                Ok(())
+            })
+        } else if meta.path.is_ident("crate") {
+            args.krate = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported com_interface argument"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let item = parse_macro_input!(item as ItemTrait);
+
+    match expand(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Args {
+    iid: Option<Expr>,
+    bases: Vec<Path>,
+    krate: Option<Path>,
+}
+
+struct Method {
+    attrs: Vec<syn::Attribute>,
+    name: Ident,
+    arg_names: Vec<Ident>,
+    arg_types: Vec<Type>,
+    output: ReturnType,
+}
+
+fn parse_method(item: &TraitItem) -> syn::Result<Method> {
+    let TraitItem::Fn(method) = item else {
+        return Err(Error::new(
+            item.span(),
+            "COM interfaces can only contain methods",
+        ));
+    };
+
+    let sig = &method.sig;
+    if sig.unsafety.is_none() {
+        return Err(Error::new(
+            sig.span(),
+            "COM interface methods must be unsafe",
+        ));
+    }
+    if method.default.is_some() {
+        return Err(Error::new(
+            method.default.span(),
+            "COM interface methods cannot have default implementations",
+        ));
+    }
+    if !sig.generics.params.is_empty() || sig.asyncness.is_some() || sig.variadic.is_some() {
+        return Err(Error::new(
+            sig.span(),
+            "COM interface methods cannot be generic, async, or variadic",
+        ));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => {
+            return Err(Error::new(
+                sig.span(),
+                "COM interface methods must take `&self` as their first argument",
+            ))
+        }
+    }
+
+    let mut arg_names = Vec::new();
+    let mut arg_types = Vec::new();
+    for input in inputs {
+        let FnArg::Typed(arg) = input else {
+            return Err(Error::new(input.span(), "unexpected `self` argument"));
+        };
+        let Pat::Ident(pat) = &*arg.pat else {
+            return Err(Error::new(
+                arg.pat.span(),
+                "COM interface method arguments must be identifiers",
+            ));
+        };
+
+        arg_names.push(pat.ident.clone());
+        arg_types.push((*arg.ty).clone());
+    }
+
+    Ok(Method {
+        attrs: method.attrs.clone(),
+        name: sig.ident.clone(),
+        arg_names,
+        arg_types,
+        output: sig.output.clone(),
+    })
+}
+
+fn vtbl_path(path: &Path) -> Path {
+    let mut path = path.clone();
+    let last = path.segments.last_mut().unwrap();
+    last.ident = format_ident!("{}Vtbl", last.ident);
+    path
+}
+
+fn expand(args: Args, item: ItemTrait) -> syn::Result<TokenStream2> {
+    let Some(iid) = args.iid else {
+        return Err(Error::new(
+            item.ident.span(),
+            "missing `iid` argument for com_interface",
+        ));
+    };
+    let Some(base) = args.bases.first() else {
+        return Err(Error::new(
+            item.ident.span(),
+            "com_interface requires at least one base interface, e.g. `bases(IUnknown)`",
+        ));
+    };
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "COM interfaces cannot be generic",
+        ));
+    }
+
+    let krate = args
+        .krate
+        .unwrap_or_else(|| syn::parse_quote!(::com_scrape_types));
+    let bases = &args.bases;
+    let other_bases = &args.bases[1..];
+    let base_vtbl = vtbl_path(base);
+
+    let methods = item
+        .items
+        .iter()
+        .map(parse_method)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let attrs = &item.attrs;
+    let vis = &item.vis;
+    let name = &item.ident;
+    let trait_name = format_ident!("{}Trait", name);
+    let vtbl_name = format_ident!("{}Vtbl", name);
+    let supertraits = &item.supertraits;
+    let colon = item.colon_token;
+
+    let method_attrs = methods.iter().map(|m| &m.attrs).collect::<Vec<_>>();
+    let method_names = methods.iter().map(|m| &m.name).collect::<Vec<_>>();
+    let arg_names = methods.iter().map(|m| &m.arg_names).collect::<Vec<_>>();
+    let arg_types = methods.iter().map(|m| &m.arg_types).collect::<Vec<_>>();
+    let outputs = methods.iter().map(|m| &m.output).collect::<Vec<_>>();
+
+    Ok(quote! {
+        #(#attrs)*
+        #[repr(C)]
+        #[derive(Copy, Clone)]
+        #vis struct #name {
+            pub vtbl: *const #vtbl_name,
+        }
+
+        unsafe impl Send for #name {}
+        unsafe impl Sync for #name {}
+
+        #[repr(C)]
+        #vis struct #vtbl_name {
+            pub base: #base_vtbl,
+            #(
+                pub #method_names: unsafe extern "system" fn(
+                    this: *mut #name,
+                    #(#arg_names: #arg_types,)*
+                ) #outputs,
+            )*
+        }
+
+        impl #krate::Unknown for #name {
+            #[inline]
+            unsafe fn query_interface(
+                this: *mut Self,
+                iid: &#krate::Guid,
+            ) -> Option<*mut ::core::ffi::c_void> {
+                <#base as #krate::Unknown>::query_interface(this as *mut #base, iid)
+            }
+
+            #[inline]
+            unsafe fn add_ref(this: *mut Self) -> usize {
+                <#base as #krate::Unknown>::add_ref(this as *mut #base)
+            }
+
+            #[inline]
+            unsafe fn release(this: *mut Self) -> usize {
+                <#base as #krate::Unknown>::release(this as *mut #base)
+            }
+        }
+
+        unsafe impl #krate::Interface for #name {
+            type Vtbl = #vtbl_name;
+
+            const IID: #krate::Guid = #iid;
+
+            #[inline]
+            fn inherits(iid: &#krate::Guid) -> bool {
+                iid == &Self::IID || <#base as #krate::Interface>::inherits(iid)
+            }
+        }
+
+        #(unsafe impl #krate::Inherits<#bases> for #name {})*
+
+        // The virtual table only contains the first base's virtual table, so implementing
+        // `Inherits` for any other base is only sound if the first base already inherits from it.
+        const _: () = {
+            #[allow(dead_code)]
+            fn assert_inherits<I: #krate::Inherits<J>, J: #krate::Interface>() {}
+
+            #[allow(dead_code)]
+            fn assert_bases() {
+                #(assert_inherits::<#base, #other_bases>();)*
+            }
+        };
+
+        #vis trait #trait_name #colon #supertraits {
+            #(
+                #(#method_attrs)*
+                unsafe fn #method_names(&self, #(#arg_names: #arg_types),*) #outputs;
+            )*
+        }
+
+        impl<P> #trait_name for P
+        where
+            P: #krate::SmartPtr,
+            P::Target: #krate::Inherits<#name>,
+            #(P::Target: #krate::Inherits<#bases>,)*
+        {
+            #(
+                #[inline]
+                unsafe fn #method_names(&self, #(#arg_names: #arg_types),*) #outputs {
+                    let ptr = #krate::SmartPtr::ptr(self) as *mut #name;
+                    ((*(*ptr).vtbl).#method_names)(ptr, #(#arg_names),*)
+                }
+            )*
+        }
+
+        impl #name {
+            pub const fn make_vtbl<C, W, const OFFSET: isize>() -> #vtbl_name
+            where
+                C: #trait_name + #krate::Class,
+                W: #krate::Wrapper<C>,
+            {
+                #(
+                    unsafe extern "system" fn #method_names<C, W, const OFFSET: isize>(
+                        this: *mut #name,
+                        #(#arg_names: #arg_types,)*
+                    ) #outputs
+                    where
+                        C: #trait_name + #krate::Class,
+                        W: #krate::Wrapper<C>,
+                    {
+                        let header_ptr =
+                            (this as *mut u8).offset(-OFFSET) as *mut #krate::Header<C>;
+                        let ptr = W::data_from_header(header_ptr);
+                        (*ptr).#method_names(#(#arg_names),*)
+                    }
+                )*
+
+                #vtbl_name {
+                    base: <#base>::make_vtbl::<C, W, OFFSET>(),
+                    #(#method_names: #method_names::<C, W, OFFSET>,)*
+                }
+            }
+        }
+
+        unsafe impl<C, W, const OFFSET: isize> #krate::Construct<C, W, OFFSET> for #name
+        where
+            C: #trait_name + #krate::Class,
+            W: #krate::Wrapper<C>,
+        {
+            const OBJ: #name = #name {
+                vtbl: &Self::make_vtbl::<C, W, OFFSET>(),
+            };
+        }
+    })
+}
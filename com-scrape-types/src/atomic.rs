@@ -0,0 +1,120 @@
+use core::hint;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use super::{ComPtr, Interface};
+
+/// An optional [`ComPtr`] which can be shared between threads and updated atomically.
+///
+/// `AtomicComPtr` is intended for slots which are read frequently and written rarely, such as a
+/// plugin's current component handler or connected peer. [`load`](Self::load) never blocks: it
+/// performs a bounded number of atomic operations plus a single call to the object's add-ref
+/// method, which makes it suitable for use from real-time threads. [`store`](Self::store) and
+/// [`swap`](Self::swap) wait for any concurrent calls to [`load`](Self::load) to finish before
+/// releasing or returning the previous pointer, so they should not be called from real-time
+/// threads.
+///
+/// `AtomicComPtr` holds a reference to the object it points to, i.e. it will decrement the
+/// object's reference count when the pointer is replaced or when the `AtomicComPtr` is dropped.
+pub struct AtomicComPtr<I: Interface> {
+    ptr: AtomicPtr<I>,
+    readers: AtomicUsize,
+}
+
+unsafe impl<I: Interface + Send + Sync> Send for AtomicComPtr<I> {}
+unsafe impl<I: Interface + Send + Sync> Sync for AtomicComPtr<I> {}
+
+impl<I: Interface> Default for AtomicComPtr<I> {
+    #[inline]
+    fn default() -> AtomicComPtr<I> {
+        AtomicComPtr::new(None)
+    }
+}
+
+impl<I: Interface> Drop for AtomicComPtr<I> {
+    #[inline]
+    fn drop(&mut self) {
+        let ptr = *self.ptr.get_mut();
+        unsafe {
+            drop(ComPtr::from_raw(ptr));
+        }
+    }
+}
+
+impl<I: Interface> AtomicComPtr<I> {
+    /// Creates a new `AtomicComPtr` holding `ptr`.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn new(ptr: Option<ComPtr<I>>) -> AtomicComPtr<I> {
+        AtomicComPtr {
+            ptr: AtomicPtr::new(ptr.map_or(ptr::null_mut(), ComPtr::into_raw)),
+            readers: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a [`ComPtr`] to the object currently held by the `AtomicComPtr`, if any.
+    ///
+    /// If a [`ComPtr`] is returned, the object's reference count will be incremented.
+    #[inline]
+    pub fn load(&self) -> Option<ComPtr<I>> {
+        self.readers.fetch_add(1, Ordering::SeqCst);
+
+        let ptr = self.ptr.load(Ordering::SeqCst);
+        let result = unsafe { ComPtr::from_raw(ptr) };
+        if let Some(result) = &result {
+            unsafe {
+                I::add_ref(result.as_ptr());
+            }
+        }
+
+        self.readers.fetch_sub(1, Ordering::SeqCst);
+
+        result
+    }
+
+    /// Replaces the pointer held by the `AtomicComPtr` with `ptr`, returning the previous one.
+    ///
+    /// Does not perform any reference counting operations. Waits for any concurrent calls to
+    /// [`load`](Self::load) to finish before returning.
+    #[inline]
+    pub fn swap(&self, ptr: Option<ComPtr<I>>) -> Option<ComPtr<I>> {
+        let new = ptr.map_or(ptr::null_mut(), ComPtr::into_raw);
+        let old = self.ptr.swap(new, Ordering::SeqCst);
+
+        // A concurrent call to load may have read the old pointer but not yet incremented its
+        // reference count, so wait until all such calls have finished before giving up the
+        // reference held by this AtomicComPtr.
+        while self.readers.load(Ordering::SeqCst) != 0 {
+            hint::spin_loop();
+        }
+
+        unsafe { ComPtr::from_raw(old) }
+    }
+
+    /// Replaces the pointer held by the `AtomicComPtr` with `ptr`.
+    ///
+    /// Decrements the reference count of the previously held object, if any. Waits for any
+    /// concurrent calls to [`load`](Self::load) to finish before doing so.
+    #[inline]
+    pub fn store(&self, ptr: Option<ComPtr<I>>) {
+        drop(self.swap(ptr));
+    }
+
+    /// Takes the pointer held by the `AtomicComPtr`, leaving it empty.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn take(&self) -> Option<ComPtr<I>> {
+        self.swap(None)
+    }
+
+    /// Consumes the `AtomicComPtr`, returning the pointer it holds.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<ComPtr<I>> {
+        let ptr = core::mem::replace(self.ptr.get_mut(), ptr::null_mut());
+        unsafe { ComPtr::from_raw(ptr) }
+    }
+}
@@ -1,22 +1,18 @@
-use std::ops::Deref;
-use std::ptr::addr_of;
-use std::sync::Arc;
+use alloc::rc::Rc;
+use alloc::sync::{Arc, Weak};
+use core::any::TypeId;
+use core::ffi::c_void;
+use core::hash::{Hash, Hasher};
+use core::mem::{self, ManuallyDrop};
+use core::ops::Deref;
+use core::ptr::{self, addr_of, NonNull};
+#[cfg(debug_assertions)]
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(all(debug_assertions, feature = "std"))]
+use std::thread::{self, ThreadId};
 
-use super::{ComPtr, ComRef, Guid, Interface};
-
-macro_rules! offset_of {
-    ($struct:ty, $field:tt) => {{
-        use ::std::ffi::c_void;
-        use ::std::mem::MaybeUninit;
-        use ::std::ptr::addr_of;
-
-        let dummy = MaybeUninit::<$struct>::uninit();
-        let base = dummy.as_ptr();
-        let field = addr_of!((*base).$field);
-
-        (field as *const c_void).offset_from(base as *const c_void)
-    }};
-}
+use super::ptr::WeakOps;
+use super::{ComPtr, ComRef, ComWeak, Guid, Interface};
 
 /// Helper functionality used in generated virtual tables for Rust types.
 ///
@@ -24,8 +20,8 @@ macro_rules! offset_of {
 /// `com-scrape` to avoid hard-coding any particular reference counting logic or class layout, and
 /// to instead allow this logic to be plugged in at the point where the COM object is constructed.
 ///
-/// Currently, the only type implementing this trait is [`ComWrapper`], but it would be possible
-/// for additional wrapper types to implement it in the future.
+/// This trait is implemented by [`ComWrapper`], [`ComWrapperLocal`], and [`ComWrapperStatic`], but
+/// it would be possible for additional wrapper types to implement it in the future.
 pub trait Wrapper<C: Class + ?Sized> {
     /// Given a pointer to an object's header, returns a pointer to the object itself.
     unsafe fn data_from_header(ptr: *mut Header<C>) -> *mut C;
@@ -38,6 +34,47 @@ pub trait Wrapper<C: Class + ?Sized> {
 
     /// Decrements the reference count of an object and returns the resulting count.
     unsafe fn release(ptr: *mut C) -> usize;
+
+    /// If `iid` is the private identifier which the wrapper uses to recognize its own objects,
+    /// increments the reference count of the object and returns a pointer from which the wrapper
+    /// can recover it. Otherwise, returns `None`.
+    ///
+    /// Implementations of `query_interface` for Rust classes should call this before looking up
+    /// `iid` in the class's interface list, in order to support
+    /// [`ComWrapper::try_from_com_ptr`]. The default implementation always returns `None`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live object managed by this wrapper, i.e. one for which
+    /// [`header_from_data`](Self::header_from_data) returns a valid header.
+    ///
+    /// The returned pointer is opaque, and may only be converted back into a wrapper by the wrapper
+    /// which returned it. It owns the reference that was added by this call, so it remains valid
+    /// until that reference is released, even if all other references to the object are dropped.
+    unsafe fn query_wrapper(_ptr: *mut C, _iid: &Guid) -> Option<*mut c_void> {
+        None
+    }
+
+    /// If the object is aggregated by another object (its controlling unknown), forwards a
+    /// `query_interface` call to the controlling unknown and returns `Some` containing the result.
+    /// Otherwise, returns `None`.
+    ///
+    /// Implementations of `query_interface` for Rust classes should call this after
+    /// [`query_wrapper`](Self::query_wrapper), and if it returns `Some`, use its contents as the
+    /// result of the call instead of looking up `iid` in the class's interface list. The default
+    /// implementation always returns `None`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a live object managed by this wrapper, i.e. one for which
+    /// [`header_from_data`](Self::header_from_data) returns a valid header.
+    ///
+    /// The inner result has the same meaning as the result of `query_interface` on the controlling
+    /// unknown: if it is `Some`, it is an interface pointer which owns a new reference, and it
+    /// remains valid until that reference is released through its own `release` method.
+    unsafe fn query_outer(_ptr: *mut C, _iid: &Guid) -> Option<Option<*mut c_void>> {
+        None
+    }
 }
 
 /// Generates the virtual table and base class object for a given class and interface.
@@ -47,7 +84,9 @@ pub trait Wrapper<C: Class + ?Sized> {
 /// the constructed base class object will be located.
 ///
 /// This trait is used in the implementations of [`MakeHeader`] for tuples of interfaces. Interface
-/// types generated by `com-scrape` will implement this trait.
+/// types generated by `com-scrape` will implement this trait. Interfaces defined by hand (see the
+/// [crate-level documentation](crate#defining-interfaces-outside-of-generated-bindings)) must also
+/// implement it in order to be usable in a [`Class::Interfaces`] list.
 ///
 /// # Safety
 ///
@@ -80,8 +119,26 @@ pub unsafe trait InterfaceList {
     /// from an interface whose GUID equals `iid`, `query` returns the offset of the corresponding
     /// base class object within `Self::Header`.
     fn query(iid: &Guid) -> Option<isize>;
+
+    /// The GUID of each interface in the list, along with its [`Interface::inherits`] function.
+    ///
+    /// Used by [`assert_com_object_valid`](crate::assert_com_object_valid) to determine which
+    /// interfaces to check, so it must contain every interface in the list.
+    const INTERFACES: &'static [InterfaceEntry];
+
+    /// If there is an interface in the list whose GUID equals `iid`, returns its Rust type name.
+    ///
+    /// Used for diagnostics, such as the events emitted by
+    /// [`trace_query_interface`](crate::trace_query_interface).
+    fn name(_iid: &Guid) -> Option<&'static str> {
+        None
+    }
 }
 
+/// An entry in [`InterfaceList::INTERFACES`]: the GUID of an interface, along with its
+/// [`Interface::inherits`] function.
+pub type InterfaceEntry = (Guid, fn(&Guid) -> bool);
+
 /// Generates the object header for a given class and list of interfaces.
 ///
 /// This trait is implemented for tuples of interface types, and it is used by [`ComWrapper`] to
@@ -103,6 +160,19 @@ where
 /// A Rust type that defines a COM class.
 ///
 /// Must be implemented for a type to be used with [`ComWrapper`].
+///
+/// Generic types may implement `Class` like any other trait, with each instantiation receiving its
+/// own virtual tables:
+///
+/// ```ignore
+/// struct MyClass<T: Sample> {
+///     buffer: Vec<T>,
+/// }
+///
+/// impl<T: Sample> Class for MyClass<T> {
+///     type Interfaces = (ISomeInterface,);
+/// }
+/// ```
 pub trait Class {
     /// The list of interfaces implemented by this Rust type.
     ///
@@ -116,6 +186,19 @@ pub trait Class {
     /// }
     /// ```
     type Interfaces: InterfaceList;
+
+    /// Called by [`ComWrapper`] or [`ComWrapperLocal`] when the object's reference count reaches
+    /// zero, before the object is destroyed.
+    ///
+    /// While `pre_destroy` runs, the object (including its virtual tables and the interface
+    /// pointers to it) is still fully intact, so this is the place to unregister the object from
+    /// anything which may still call into it (e.g. a host's run loop). However, the reference
+    /// count has already reached zero, so new references to the object must not be created.
+    ///
+    /// After `pre_destroy` returns, the value is dropped, and then its memory is freed. The default
+    /// implementation does nothing.
+    #[inline]
+    fn pre_destroy(&self) {}
 }
 
 /// Convenience alias for getting the object header of a [`Class`].
@@ -132,7 +215,21 @@ macro_rules! interface_list {
             fn query(iid: &Guid) -> Option<isize> {
                 $(
                     if $interface::inherits(iid) {
-                        return Some($index * std::mem::size_of::<*mut ()>() as isize);
+                        return Some($index * core::mem::size_of::<*mut ()>() as isize);
+                    }
+                )*
+
+                None
+            }
+
+            const INTERFACES: &'static [InterfaceEntry] = &[
+                $(($interface::IID, $interface::inherits as fn(&Guid) -> bool)),*
+            ];
+
+            fn name(iid: &Guid) -> Option<&'static str> {
+                $(
+                    if iid == &$interface::IID {
+                        return Some(core::any::type_name::<$interface>());
                     }
                 )*
 
@@ -144,7 +241,7 @@ macro_rules! interface_list {
         where
             C: Class,
             W: Wrapper<C>,
-            $($interface: Construct<C, W, { $index * std::mem::size_of::<*mut ()>() as isize }>,)*
+            $($interface: Construct<C, W, { $index * core::mem::size_of::<*mut ()>() as isize }>,)*
         {
             const HEADER: Self::Header = $header($($interface::OBJ),*);
         }
@@ -171,12 +268,171 @@ interface_list!(Header15, I0 0, I1 1, I2 2, I3 3, I4 4, I5 5, I6 6, I7 7, I8 8,
 struct ComWrapperInner<C: Class> {
     header: Header<C>,
     data: C,
+    // The number of references held via interface pointers (as opposed to via ComWrapper), used to
+    // detect releases of references which were never acquired.
+    #[cfg(debug_assertions)]
+    com_refs: AtomicUsize,
+    // For objects created by ComWrapperLocal, the thread on which the object was created, used to
+    // detect reference counting operations from other threads.
+    #[cfg(all(debug_assertions, feature = "std"))]
+    thread: Option<ThreadId>,
+    // For aggregated objects, the controlling unknown, to which all reference counting operations
+    // and query_interface calls made via interface pointers are forwarded.
+    outer: Option<Outer>,
+    // The identifier under which the object is registered in the registry of live objects.
+    #[cfg(feature = "leak-tracking")]
+    leak_id: u64,
+}
+
+// The controlling unknown of an aggregated object. No reference to it is held, since it owns the
+// aggregated object.
+struct Outer {
+    ptr: *mut c_void,
+    query_interface: unsafe fn(*mut c_void, &Guid) -> Option<*mut c_void>,
+    add_ref: unsafe fn(*mut c_void) -> usize,
+    release: unsafe fn(*mut c_void) -> usize,
+}
+
+impl Outer {
+    fn new<U: Interface>(ptr: *mut U) -> Outer {
+        unsafe fn query_interface<U: Interface>(
+            ptr: *mut c_void,
+            iid: &Guid,
+        ) -> Option<*mut c_void> {
+            U::query_interface(ptr as *mut U, iid)
+        }
+
+        unsafe fn add_ref<U: Interface>(ptr: *mut c_void) -> usize {
+            U::add_ref(ptr as *mut U)
+        }
+
+        unsafe fn release<U: Interface>(ptr: *mut c_void) -> usize {
+            U::release(ptr as *mut U)
+        }
+
+        Outer {
+            ptr: ptr as *mut c_void,
+            query_interface: query_interface::<U>,
+            add_ref: add_ref::<U>,
+            release: release::<U>,
+        }
+    }
+}
+
+impl<C: Class> ComWrapperInner<C> {
+    const HEADER_OFFSET: isize = mem::offset_of!(Self, header) as isize;
+    const DATA_OFFSET: isize = mem::offset_of!(Self, data) as isize;
+
+    #[inline]
+    fn new(header: Header<C>, data: C, local: bool, outer: Option<Outer>) -> ComWrapperInner<C> {
+        #[cfg(not(all(debug_assertions, feature = "std")))]
+        let _ = local;
+
+        ComWrapperInner {
+            header,
+            data,
+            #[cfg(debug_assertions)]
+            com_refs: AtomicUsize::new(0),
+            #[cfg(all(debug_assertions, feature = "std"))]
+            thread: local.then(|| thread::current().id()),
+            outer,
+            #[cfg(feature = "leak-tracking")]
+            leak_id: super::leak::register(core::any::type_name::<C>()),
+        }
+    }
+
+    // Moves the data out of the object without calling pre_destroy.
+    #[inline]
+    fn into_data(self) -> C {
+        let mut this = ManuallyDrop::new(self);
+
+        #[cfg(feature = "leak-tracking")]
+        super::leak::unregister(this.leak_id);
+
+        unsafe {
+            ptr::drop_in_place(&mut this.header);
+            ptr::read(&this.data)
+        }
+    }
+
+    // Called when a reference to the object is acquired via an interface pointer.
+    #[inline]
+    fn acquire_com_ref(&self) {
+        #[cfg(debug_assertions)]
+        {
+            self.check_thread();
+            self.com_refs.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Called when a reference to the object held via an interface pointer is released.
+    #[inline]
+    fn release_com_ref(&self) {
+        // In debug builds, catch a release of a reference which was never acquired before it can
+        // drop a reference owned by a ComWrapper (or free the object while it is still in use).
+        // Releases after the object has already been freed cannot be detected.
+        #[cfg(debug_assertions)]
+        {
+            self.check_thread();
+            if self
+                .com_refs
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    count.checked_sub(1)
+                })
+                .is_err()
+            {
+                panic!(
+                    "release called on a COM object with no outstanding interface pointer references"
+                );
+            }
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[inline]
+    fn check_thread(&self) {
+        #[cfg(feature = "std")]
+        if let Some(thread) = self.thread {
+            assert!(
+                thread == thread::current().id(),
+                "reference count of a ComWrapperLocal object modified from another thread"
+            );
+        }
+    }
+
+    // Returns a pointer to the base class object at `offset` within the header of the object
+    // that `ptr` points to.
+    #[inline]
+    unsafe fn interface_ptr<I>(ptr: *const ComWrapperInner<C>, offset: isize) -> *mut I {
+        (ptr as *mut u8)
+            .offset(ComWrapperInner::<C>::HEADER_OFFSET)
+            .offset(offset) as *mut I
+    }
+}
+
+// pre_destroy runs before any fields are dropped, so the header is still valid at that point.
+// Fields are then dropped in declaration order, so the header is dropped before the data, but
+// since it only contains pointers to static virtual tables, dropping it does nothing, and interface
+// pointers to the object remain intact until its memory is freed.
+impl<C: Class> Drop for ComWrapperInner<C> {
+    fn drop(&mut self) {
+        self.data.pre_destroy();
+
+        #[cfg(feature = "leak-tracking")]
+        super::leak::unregister(self.leak_id);
+    }
 }
 
 /// A wrapper for constructing a reference-counted COM object from a Rust value.
 ///
 /// `ComWrapper` represents an owning reference to the COM object, i.e. it will decrement the
 /// object's reference count when it goes out of scope.
+///
+/// In debug builds, `ComWrapper` keeps track of how many references to the object are held via
+/// interface pointers, and panics if the object's release method is called when there are none
+/// (e.g. because a host releases an interface pointer one time too many). Since the release
+/// method is called via an `extern "system"` function, this results in an abort with a
+/// diagnostic message rather than a silently corrupted reference count.
 pub struct ComWrapper<C: Class> {
     inner: Arc<ComWrapperInner<C>>,
 }
@@ -201,25 +457,72 @@ impl<C: Class> Deref for ComWrapper<C> {
     }
 }
 
-impl<C: Class> Wrapper<C> for ComWrapper<C> {
+// A 64-bit FNV-1a hasher, used since std's DefaultHasher is not available without std.
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    fn new() -> FnvHasher {
+        FnvHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// Returns the private identifier used to recognize objects of type ComWrapper<C>. The address of a
+// static is included so that objects created by a different copy of this crate (e.g. in another
+// plugin binary loaded into the same process) are never recognized, even if their types match.
+fn wrapper_iid<C: 'static>() -> Guid {
+    static MODULE: u8 = 0;
+
+    let mut hasher = FnvHasher::new();
+    TypeId::of::<C>().hash(&mut hasher);
+    (addr_of!(MODULE) as usize).hash(&mut hasher);
+    let first = hasher.finish();
+    first.hash(&mut hasher);
+    let second = hasher.finish();
+
+    let mut iid = [0; 16];
+    iid[..8].copy_from_slice(&first.to_le_bytes());
+    iid[8..].copy_from_slice(&second.to_le_bytes());
+    iid
+}
+
+impl<C: Class + 'static> Wrapper<C> for ComWrapper<C> {
     #[inline]
     unsafe fn data_from_header(ptr: *mut Header<C>) -> *mut C {
         (ptr as *mut u8)
-            .offset(-offset_of!(ComWrapperInner<C>, header))
-            .offset(offset_of!(ComWrapperInner<C>, data)) as *mut C
+            .offset(-ComWrapperInner::<C>::HEADER_OFFSET)
+            .offset(ComWrapperInner::<C>::DATA_OFFSET) as *mut C
     }
 
     #[inline]
     unsafe fn header_from_data(ptr: *mut C) -> *mut Header<C> {
         (ptr as *mut u8)
-            .offset(-offset_of!(ComWrapperInner<C>, data))
-            .offset(offset_of!(ComWrapperInner<C>, header)) as *mut Header<C>
+            .offset(-ComWrapperInner::<C>::DATA_OFFSET)
+            .offset(ComWrapperInner::<C>::HEADER_OFFSET) as *mut Header<C>
     }
 
     #[inline]
     unsafe fn add_ref(ptr: *mut C) -> usize {
-        let wrapper_ptr = (ptr as *mut u8).offset(-offset_of!(ComWrapperInner<C>, data))
-            as *mut ComWrapperInner<C>;
+        let wrapper_ptr =
+            (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET) as *mut ComWrapperInner<C>;
+
+        if let Some(outer) = &(*wrapper_ptr).outer {
+            return (outer.add_ref)(outer.ptr);
+        }
+
+        (*wrapper_ptr).acquire_com_ref();
 
         let arc = Arc::from_raw(wrapper_ptr);
         let result = Arc::strong_count(&arc) + 1;
@@ -232,8 +535,14 @@ impl<C: Class> Wrapper<C> for ComWrapper<C> {
 
     #[inline]
     unsafe fn release(ptr: *mut C) -> usize {
-        let wrapper_ptr = (ptr as *mut u8).offset(-offset_of!(ComWrapperInner<C>, data))
-            as *mut ComWrapperInner<C>;
+        let wrapper_ptr =
+            (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET) as *mut ComWrapperInner<C>;
+
+        if let Some(outer) = &(*wrapper_ptr).outer {
+            return (outer.release)(outer.ptr);
+        }
+
+        (*wrapper_ptr).release_com_ref();
 
         let arc = Arc::from_raw(wrapper_ptr);
         let result = Arc::strong_count(&arc) - 1;
@@ -243,6 +552,28 @@ impl<C: Class> Wrapper<C> for ComWrapper<C> {
 
         result
     }
+
+    #[inline]
+    unsafe fn query_wrapper(ptr: *mut C, iid: &Guid) -> Option<*mut c_void> {
+        if iid == &wrapper_iid::<C>() {
+            let wrapper_ptr = (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET)
+                as *mut ComWrapperInner<C>;
+            Arc::increment_strong_count(wrapper_ptr);
+
+            Some(wrapper_ptr as *mut c_void)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    unsafe fn query_outer(ptr: *mut C, iid: &Guid) -> Option<Option<*mut c_void>> {
+        let wrapper_ptr =
+            (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET) as *mut ComWrapperInner<C>;
+
+        let outer = (*wrapper_ptr).outer.as_ref()?;
+        Some((outer.query_interface)(outer.ptr, iid))
+    }
 }
 
 impl<C: Class> ComWrapper<C> {
@@ -254,10 +585,99 @@ impl<C: Class> ComWrapper<C> {
         C::Interfaces: MakeHeader<C, Self>,
     {
         ComWrapper {
-            inner: Arc::new(ComWrapperInner {
-                header: C::Interfaces::HEADER,
+            inner: Arc::new(ComWrapperInner::new(
+                C::Interfaces::HEADER,
                 data,
-            }),
+                false,
+                None,
+            )),
+        }
+    }
+
+    /// Allocates memory for an object which is aggregated by another object (its controlling
+    /// unknown, `outer`), and places `data` into it.
+    ///
+    /// The `query_interface`, `add_ref`, and `release` methods of interface pointers to an
+    /// aggregated object are forwarded to the controlling unknown, as are the reference counting
+    /// operations performed by [`to_com_ptr`](Self::to_com_ptr) and [`ComWeak::upgrade`]. The
+    /// returned `ComWrapper` acts as the non-delegating unknown of the aggregated object: the
+    /// controlling unknown should hold on to it for as long as it exists, and use
+    /// [`to_com_ptr`](Self::to_com_ptr) to obtain interface pointers to the aggregated object
+    /// when queried for its interfaces.
+    ///
+    /// # Safety
+    ///
+    /// No reference to `outer` is held, so `outer` must remain valid for as long as the returned
+    /// `ComWrapper` (or any clone of it) exists. If the returned `ComWrapper` is sent to another
+    /// thread, it must also be valid to call `outer`'s methods from that thread.
+    #[inline]
+    pub unsafe fn new_aggregated<U: Interface>(data: C, outer: ComRef<U>) -> ComWrapper<C>
+    where
+        C: 'static,
+        C::Interfaces: MakeHeader<C, Self>,
+    {
+        let outer = Outer::new(outer.as_ptr());
+        ComWrapper {
+            inner: Arc::new(ComWrapperInner::new(
+                C::Interfaces::HEADER,
+                data,
+                false,
+                Some(outer),
+            )),
+        }
+    }
+
+    /// Returns a mutable reference to the wrapped value if there are no other references to the
+    /// object, whether held via another `ComWrapper`, an interface pointer, or a [`ComWeak`].
+    /// Otherwise, returns `None`.
+    #[inline]
+    pub fn get_mut(this: &mut Self) -> Option<&mut C> {
+        Arc::get_mut(&mut this.inner).map(|inner| &mut inner.data)
+    }
+
+    /// Returns the wrapped value if there are no other strong references to the object, whether
+    /// held via another `ComWrapper` or an interface pointer. Otherwise, returns `this` unchanged
+    /// as an error.
+    ///
+    /// [`Class::pre_destroy`] is not called, since the value is not destroyed.
+    #[inline]
+    pub fn try_unwrap(this: Self) -> Result<C, Self> {
+        match Arc::try_unwrap(this.inner) {
+            Ok(inner) => Ok(inner.into_data()),
+            Err(inner) => Err(ComWrapper { inner }),
+        }
+    }
+
+    /// If the object that `ptr` points to was created by a `ComWrapper<C>`, returns another
+    /// `ComWrapper<C>` pointing to the same object. Otherwise, returns `None`.
+    ///
+    /// This makes it possible to recover typed access to an object which was passed to another
+    /// component (e.g. a host) and later handed back. It relies on the `query_interface`
+    /// implementation of the object calling [`Wrapper::query_wrapper`]; for objects whose
+    /// `query_interface` implementation does not do so, `None` will be returned.
+    ///
+    /// If a `ComWrapper` is returned, the object's reference count will be incremented.
+    #[inline]
+    pub fn try_from_com_ptr<I: Interface>(ptr: &ComPtr<I>) -> Option<ComWrapper<C>>
+    where
+        C: 'static,
+    {
+        Self::try_from_com_ref(ptr.as_com_ref())
+    }
+
+    /// Equivalent to [`ComWrapper::try_from_com_ptr`], but takes a [`ComRef`].
+    ///
+    /// If a `ComWrapper` is returned, the object's reference count will be incremented.
+    #[inline]
+    pub fn try_from_com_ref<I: Interface>(ptr: ComRef<I>) -> Option<ComWrapper<C>>
+    where
+        C: 'static,
+    {
+        unsafe {
+            let wrapper_ptr = I::query_interface(ptr.as_ptr(), &wrapper_iid::<C>())?;
+            Some(ComWrapper {
+                inner: Arc::from_raw(wrapper_ptr as *const ComWrapperInner<C>),
+            })
         }
     }
 
@@ -269,9 +689,7 @@ impl<C: Class> ComWrapper<C> {
         if let Some(offset) = C::Interfaces::query(&I::IID) {
             unsafe {
                 let wrapper_ptr = Arc::as_ptr(&self.inner) as *mut ComWrapperInner<C>;
-                let interface_ptr = (wrapper_ptr as *mut u8)
-                    .offset(offset_of!(ComWrapperInner<C>, header))
-                    .offset(offset) as *mut I;
+                let interface_ptr = ComWrapperInner::<C>::interface_ptr::<I>(wrapper_ptr, offset);
                 Some(ComRef::from_raw_unchecked(interface_ptr))
             }
         } else {
@@ -286,14 +704,421 @@ impl<C: Class> ComWrapper<C> {
     pub fn to_com_ptr<I: Interface>(&self) -> Option<ComPtr<I>> {
         if let Some(offset) = C::Interfaces::query(&I::IID) {
             unsafe {
+                if let Some(outer) = &self.inner.outer {
+                    (outer.add_ref)(outer.ptr);
+                    let interface_ptr =
+                        ComWrapperInner::<C>::interface_ptr::<I>(Arc::as_ptr(&self.inner), offset);
+                    return Some(ComPtr::from_raw_unchecked(interface_ptr));
+                }
+
                 let wrapper_ptr = Arc::into_raw(self.inner.clone()) as *mut ComWrapperInner<C>;
-                let interface_ptr = (wrapper_ptr as *mut u8)
-                    .offset(offset_of!(ComWrapperInner<C>, header))
-                    .offset(offset) as *mut I;
+                (*wrapper_ptr).acquire_com_ref();
+
+                let interface_ptr = ComWrapperInner::<C>::interface_ptr::<I>(wrapper_ptr, offset);
                 Some(ComPtr::from_raw_unchecked(interface_ptr))
             }
         } else {
             None
         }
     }
+
+    /// If `I` is in `C`'s interface list, returns a [`ComWeak<I>`] pointing to the object.
+    ///
+    /// Does not increment the object's reference count.
+    #[inline]
+    pub fn downgrade<I: Interface>(&self) -> Option<ComWeak<I>> {
+        let offset = C::Interfaces::query(&I::IID)?;
+        Some(Self::com_weak(Arc::downgrade(&self.inner), offset))
+    }
+
+    /// Allocates memory for an object and its header and places the value returned by `f` into
+    /// it, passing `f` a [`ComWeak<I>`] pointing to the object.
+    ///
+    /// This makes it possible for an object to store a reference to itself, e.g. in order to pass
+    /// an interface pointer to itself to another object from within one of its interface methods.
+    /// The [`ComWeak`] cannot be upgraded until `new_cyclic` has returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `I` is not in `C`'s interface list.
+    #[inline]
+    pub fn new_cyclic<I, F>(f: F) -> ComWrapper<C>
+    where
+        C: 'static,
+        C::Interfaces: MakeHeader<C, Self>,
+        I: Interface,
+        F: FnOnce(ComWeak<I>) -> C,
+    {
+        let offset = C::Interfaces::query(&I::IID)
+            .expect("interface passed to new_cyclic is not in the class's interface list");
+
+        ComWrapper {
+            inner: Arc::new_cyclic(|weak| {
+                let data = f(Self::com_weak(weak.clone(), offset));
+                ComWrapperInner::new(C::Interfaces::HEADER, data, false, None)
+            }),
+        }
+    }
+
+    fn com_weak<I: Interface>(weak: Weak<ComWrapperInner<C>>, offset: isize) -> ComWeak<I> {
+        unsafe {
+            let wrapper_ptr = Weak::into_raw(weak);
+            let interface_ptr = ComWrapperInner::<C>::interface_ptr::<I>(wrapper_ptr, offset);
+            ComWeak::from_raw_parts(
+                NonNull::new_unchecked(interface_ptr),
+                wrapper_ptr as *const c_void,
+                weak_ops::<C>(),
+            )
+        }
+    }
+}
+
+fn weak_ops<C: Class>() -> WeakOps {
+    unsafe fn upgrade<C: Class>(ptr: *const c_void) -> bool {
+        let weak = ManuallyDrop::new(Weak::from_raw(ptr as *const ComWrapperInner<C>));
+        if let Some(arc) = weak.upgrade() {
+            if let Some(outer) = &arc.outer {
+                (outer.add_ref)(outer.ptr);
+                return true;
+            }
+
+            arc.acquire_com_ref();
+
+            let _ = Arc::into_raw(arc);
+            true
+        } else {
+            false
+        }
+    }
+
+    unsafe fn clone<C: Class>(ptr: *const c_void) {
+        let weak = ManuallyDrop::new(Weak::from_raw(ptr as *const ComWrapperInner<C>));
+        let _ = Weak::into_raw((*weak).clone());
+    }
+
+    unsafe fn release<C: Class>(ptr: *const c_void) {
+        drop(Weak::from_raw(ptr as *const ComWrapperInner<C>));
+    }
+
+    WeakOps {
+        upgrade: upgrade::<C>,
+        clone: clone::<C>,
+        release: release::<C>,
+    }
+}
+
+/// A single-threaded variant of [`ComWrapper`].
+///
+/// `ComWrapperLocal` is identical to [`ComWrapper`], except that it uses non-atomic reference
+/// counting. This avoids the cost of atomic operations for objects which are only ever used from
+/// a single thread (e.g. `IPlugView` implementations, which are only accessed from the UI thread).
+///
+/// `ComWrapperLocal` is neither [`Send`] nor [`Sync`], but this cannot prevent interface pointers
+/// to the object from being passed to other threads. For this reason, the functions which return
+/// interface pointers to the object ([`as_com_ref`](Self::as_com_ref) and
+/// [`to_com_ptr`](Self::to_com_ptr)) are `unsafe`, and it is the responsibility of the caller to
+/// ensure that all reference counting operations on the object (including those performed by
+/// other components such as a host) happen on the thread which created it. In debug builds with the
+/// `std` feature enabled, `ComWrapperLocal` panics if the object's reference count is modified from
+/// any other thread.
+pub struct ComWrapperLocal<C: Class> {
+    inner: Rc<ComWrapperInner<C>>,
+}
+
+impl<C: Class> Clone for ComWrapperLocal<C> {
+    fn clone(&self) -> ComWrapperLocal<C> {
+        ComWrapperLocal {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C: Class> Deref for ComWrapperLocal<C> {
+    type Target = C;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner.data
+    }
+}
+
+impl<C: Class + 'static> Wrapper<C> for ComWrapperLocal<C> {
+    #[inline]
+    unsafe fn data_from_header(ptr: *mut Header<C>) -> *mut C {
+        (ptr as *mut u8)
+            .offset(-ComWrapperInner::<C>::HEADER_OFFSET)
+            .offset(ComWrapperInner::<C>::DATA_OFFSET) as *mut C
+    }
+
+    #[inline]
+    unsafe fn header_from_data(ptr: *mut C) -> *mut Header<C> {
+        (ptr as *mut u8)
+            .offset(-ComWrapperInner::<C>::DATA_OFFSET)
+            .offset(ComWrapperInner::<C>::HEADER_OFFSET) as *mut Header<C>
+    }
+
+    #[inline]
+    unsafe fn add_ref(ptr: *mut C) -> usize {
+        let wrapper_ptr =
+            (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET) as *mut ComWrapperInner<C>;
+
+        (*wrapper_ptr).acquire_com_ref();
+
+        Rc::increment_strong_count(wrapper_ptr);
+
+        let rc = ManuallyDrop::new(Rc::from_raw(wrapper_ptr));
+        Rc::strong_count(&rc)
+    }
+
+    #[inline]
+    unsafe fn release(ptr: *mut C) -> usize {
+        let wrapper_ptr =
+            (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET) as *mut ComWrapperInner<C>;
+
+        (*wrapper_ptr).release_com_ref();
+
+        let rc = Rc::from_raw(wrapper_ptr);
+        let result = Rc::strong_count(&rc) - 1;
+        drop(rc);
+
+        result
+    }
+
+    #[inline]
+    unsafe fn query_wrapper(ptr: *mut C, iid: &Guid) -> Option<*mut c_void> {
+        if iid == &wrapper_iid::<ComWrapperLocal<C>>() {
+            let wrapper_ptr = (ptr as *mut u8).offset(-ComWrapperInner::<C>::DATA_OFFSET)
+                as *mut ComWrapperInner<C>;
+            Rc::increment_strong_count(wrapper_ptr);
+
+            Some(wrapper_ptr as *mut c_void)
+        } else {
+            None
+        }
+    }
+}
+
+impl<C: Class> ComWrapperLocal<C> {
+    /// Allocates memory for an object and its header and places `data` into it.
+    #[inline]
+    pub fn new(data: C) -> ComWrapperLocal<C>
+    where
+        C: 'static,
+        C::Interfaces: MakeHeader<C, Self>,
+    {
+        ComWrapperLocal {
+            inner: Rc::new(ComWrapperInner::new(
+                C::Interfaces::HEADER,
+                data,
+                true,
+                None,
+            )),
+        }
+    }
+
+    /// Returns a mutable reference to the wrapped value if there are no other references to the
+    /// object, whether held via another `ComWrapperLocal`, an interface pointer, or a [`ComWeak`].
+    /// Otherwise, returns `None`.
+    #[inline]
+    pub fn get_mut(this: &mut Self) -> Option<&mut C> {
+        Rc::get_mut(&mut this.inner).map(|inner| &mut inner.data)
+    }
+
+    /// Returns the wrapped value if there are no other strong references to the object, whether
+    /// held via another `ComWrapperLocal` or an interface pointer. Otherwise, returns `this`
+    /// unchanged as an error.
+    ///
+    /// [`Class::pre_destroy`] is not called, since the value is not destroyed.
+    #[inline]
+    pub fn try_unwrap(this: Self) -> Result<C, Self> {
+        match Rc::try_unwrap(this.inner) {
+            Ok(inner) => Ok(inner.into_data()),
+            Err(inner) => Err(ComWrapperLocal { inner }),
+        }
+    }
+
+    /// If the object that `ptr` points to was created by a `ComWrapperLocal<C>`, returns another
+    /// `ComWrapperLocal<C>` pointing to the same object. Otherwise, returns `None`.
+    ///
+    /// See [`ComWrapper::try_from_com_ptr`] for more details.
+    ///
+    /// If a `ComWrapperLocal` is returned, the object's reference count will be incremented.
+    #[inline]
+    pub fn try_from_com_ptr<I: Interface>(ptr: &ComPtr<I>) -> Option<ComWrapperLocal<C>>
+    where
+        C: 'static,
+    {
+        Self::try_from_com_ref(ptr.as_com_ref())
+    }
+
+    /// Equivalent to [`ComWrapperLocal::try_from_com_ptr`], but takes a [`ComRef`].
+    ///
+    /// If a `ComWrapperLocal` is returned, the object's reference count will be incremented.
+    #[inline]
+    pub fn try_from_com_ref<I: Interface>(ptr: ComRef<I>) -> Option<ComWrapperLocal<C>>
+    where
+        C: 'static,
+    {
+        unsafe {
+            let wrapper_ptr =
+                I::query_interface(ptr.as_ptr(), &wrapper_iid::<ComWrapperLocal<C>>())?;
+            Some(ComWrapperLocal {
+                inner: Rc::from_raw(wrapper_ptr as *const ComWrapperInner<C>),
+            })
+        }
+    }
+
+    /// If `I` is in `C`'s interface list, returns a [`ComRef<I>`] pointing to the object.
+    ///
+    /// Does not perform any reference counting operations.
+    ///
+    /// # Safety
+    ///
+    /// The object's reference count is not atomic, so all reference counting operations performed
+    /// via the returned pointer, or via any interface pointer obtained from it (e.g. by casting it
+    /// or by passing it to another component), must happen on the thread which created the
+    /// object. In particular, the pointer must not be stored in a type which can be shared
+    /// between threads, such as [`AtomicComPtr`](crate::AtomicComPtr).
+    #[inline]
+    pub unsafe fn as_com_ref<'a, I: Interface>(&'a self) -> Option<ComRef<'a, I>> {
+        let offset = C::Interfaces::query(&I::IID)?;
+        let interface_ptr =
+            ComWrapperInner::<C>::interface_ptr::<I>(Rc::as_ptr(&self.inner), offset);
+        Some(ComRef::from_raw_unchecked(interface_ptr))
+    }
+
+    /// If `I` is in `C`'s interface list, returns a [`ComPtr<I>`] pointing to the object.
+    ///
+    /// If a [`ComPtr`] is returned, the object's reference count will be incremented.
+    ///
+    /// # Safety
+    ///
+    /// The object's reference count is not atomic, so all reference counting operations performed
+    /// via the returned pointer, or via any interface pointer obtained from it (e.g. by casting it
+    /// or by passing it to another component), must happen on the thread which created the
+    /// object. In particular, the pointer must not be stored in a type which can be shared
+    /// between threads, such as [`AtomicComPtr`](crate::AtomicComPtr).
+    #[inline]
+    pub unsafe fn to_com_ptr<I: Interface>(&self) -> Option<ComPtr<I>> {
+        let offset = C::Interfaces::query(&I::IID)?;
+        let wrapper_ptr = Rc::into_raw(self.inner.clone());
+        (*wrapper_ptr).acquire_com_ref();
+
+        let interface_ptr = ComWrapperInner::<C>::interface_ptr::<I>(wrapper_ptr, offset);
+        Some(ComPtr::from_raw_unchecked(interface_ptr))
+    }
+}
+
+/// A variant of [`ComWrapper`] for objects which live for the entire lifetime of the program.
+///
+/// `ComWrapperStatic` stores the object header and the wrapped value inline rather than in a heap
+/// allocation, and it can be constructed in a `const` context, so it can be placed in a `static`.
+/// This is useful for singleton objects such as a plugin factory:
+///
+/// ```ignore
+/// static FACTORY: ComWrapperStatic<Factory> = ComWrapperStatic::new(Factory {});
+///
+/// #[no_mangle]
+/// extern "system" fn GetPluginFactory() -> *mut IPluginFactory {
+///     FACTORY.as_com_ref::<IPluginFactory>().unwrap().as_ptr()
+/// }
+/// ```
+///
+/// Since the object is never destroyed, reference counting operations on it do nothing, and
+/// `add_ref` and `release` always return 1. [`Class::pre_destroy`] is never called for the object,
+/// and when a `ComWrapperStatic` is stored in a `static`, the wrapped value is never dropped.
+pub struct ComWrapperStatic<C: Class> {
+    header: Header<C>,
+    data: C,
+}
+
+// The header only contains pointers to immutable virtual tables, so sharing it between threads is
+// sound as long as sharing the wrapped value is.
+unsafe impl<C: Class> Send for ComWrapperStatic<C> where C: Send {}
+unsafe impl<C: Class> Sync for ComWrapperStatic<C> where C: Sync {}
+
+impl<C: Class> Deref for ComWrapperStatic<C> {
+    type Target = C;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<C: Class> Wrapper<C> for ComWrapperStatic<C> {
+    #[inline]
+    unsafe fn data_from_header(ptr: *mut Header<C>) -> *mut C {
+        (ptr as *mut u8)
+            .offset(-ComWrapperStatic::<C>::HEADER_OFFSET)
+            .offset(ComWrapperStatic::<C>::DATA_OFFSET) as *mut C
+    }
+
+    #[inline]
+    unsafe fn header_from_data(ptr: *mut C) -> *mut Header<C> {
+        (ptr as *mut u8)
+            .offset(-ComWrapperStatic::<C>::DATA_OFFSET)
+            .offset(ComWrapperStatic::<C>::HEADER_OFFSET) as *mut Header<C>
+    }
+
+    #[inline]
+    unsafe fn add_ref(_ptr: *mut C) -> usize {
+        1
+    }
+
+    #[inline]
+    unsafe fn release(_ptr: *mut C) -> usize {
+        1
+    }
+}
+
+impl<C: Class> ComWrapperStatic<C> {
+    const HEADER_OFFSET: isize = mem::offset_of!(Self, header) as isize;
+    const DATA_OFFSET: isize = mem::offset_of!(Self, data) as isize;
+
+    /// Places `data` into an object along with its header, without allocating.
+    #[inline]
+    pub const fn new(data: C) -> ComWrapperStatic<C>
+    where
+        C::Interfaces: MakeHeader<C, Self>,
+    {
+        ComWrapperStatic {
+            header: C::Interfaces::HEADER,
+            data,
+        }
+    }
+
+    /// If `I` is in `C`'s interface list, returns a [`ComRef<I>`] pointing to the object.
+    ///
+    /// Since reference counting operations on the object do nothing, an owning [`ComPtr`] obtained
+    /// from the returned [`ComRef`] (or from a call to `query_interface` on the object) would not
+    /// keep the object alive. This requires the object to live for the rest of the program, and
+    /// so it cannot be called on a `ComWrapperStatic` which is not stored in a `static` (or
+    /// otherwise leaked):
+    ///
+    /// ```compile_fail
+    /// use com_scrape_types::{Class, ComWrapperStatic, Interface};
+    ///
+    /// fn as_com_ref<C: Class + 'static, I: Interface>(wrapper: ComWrapperStatic<C>) {
+    ///     let _ = wrapper.as_com_ref::<I>();
+    /// }
+    /// ```
+    #[inline]
+    pub fn as_com_ref<'a, I: Interface>(&'static self) -> Option<ComRef<'a, I>> {
+        let offset = C::Interfaces::query(&I::IID)?;
+        unsafe {
+            let header_ptr = addr_of!(self.header) as *mut u8;
+            let interface_ptr = header_ptr.offset(offset) as *mut I;
+            Some(ComRef::from_raw_unchecked(interface_ptr))
+        }
+    }
+
+    /// If `I` is in `C`'s interface list, returns a [`ComPtr<I>`] pointing to the object.
+    ///
+    /// Like [`as_com_ref`](Self::as_com_ref), this requires the object to live for the rest of the
+    /// program.
+    #[inline]
+    pub fn to_com_ptr<I: Interface>(&'static self) -> Option<ComPtr<I>> {
+        self.as_com_ref().map(|ptr| ptr.to_com_ptr())
+    }
 }
@@ -0,0 +1,96 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::format;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+/// Information about an object created by [`ComWrapper`](crate::ComWrapper) or
+/// [`ComWrapperLocal`](crate::ComWrapperLocal) which has not yet been destroyed.
+///
+/// Returned by [`live_objects`].
+#[derive(Clone, Debug)]
+pub struct LiveObject {
+    /// A unique identifier for the object, assigned in order of creation.
+    pub id: u64,
+    /// The type name of the object's [`Class`](crate::Class).
+    pub type_name: &'static str,
+    /// A backtrace captured when the object was created.
+    ///
+    /// Backtraces are only captured if enabled via the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`
+    /// environment variables (see [`Backtrace::capture`]).
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static LIVE_OBJECTS: Mutex<Option<HashMap<u64, LiveObject>>> = Mutex::new(None);
+
+fn with_live_objects<R>(f: impl FnOnce(&mut HashMap<u64, LiveObject>) -> R) -> R {
+    let mut guard = LIVE_OBJECTS.lock().unwrap_or_else(|err| err.into_inner());
+    f(guard.get_or_insert_with(HashMap::new))
+}
+
+// Registers a newly created object and returns its identifier.
+pub(crate) fn register(type_name: &'static str) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+    let backtrace = Backtrace::capture();
+    let backtrace = if backtrace.status() == BacktraceStatus::Captured {
+        Some(Arc::new(backtrace))
+    } else {
+        None
+    };
+
+    let object = LiveObject {
+        id,
+        type_name,
+        backtrace,
+    };
+    with_live_objects(|objects| objects.insert(id, object));
+
+    id
+}
+
+// Unregisters an object which is being destroyed.
+pub(crate) fn unregister(id: u64) {
+    with_live_objects(|objects| objects.remove(&id));
+}
+
+/// Returns a list of the objects created by [`ComWrapper`](crate::ComWrapper) or
+/// [`ComWrapperLocal`](crate::ComWrapperLocal) which have not yet been destroyed, in order of
+/// creation.
+///
+/// Only available with the `leak-tracking` feature enabled.
+pub fn live_objects() -> Vec<LiveObject> {
+    let mut objects = with_live_objects(|objects| objects.values().cloned().collect::<Vec<_>>());
+    objects.sort_by_key(|object| object.id);
+    objects
+}
+
+/// Panics if any objects created by [`ComWrapper`](crate::ComWrapper) or
+/// [`ComWrapperLocal`](crate::ComWrapperLocal) have not yet been destroyed, listing each such
+/// object along with its creation backtrace (if captured).
+///
+/// This is intended to be called at the end of a test, after all references to the objects
+/// created during the test should have been released. Since the registry of live objects is
+/// global, tests which call `assert_no_leaks` should not run concurrently with other tests which
+/// create objects.
+///
+/// Only available with the `leak-tracking` feature enabled.
+pub fn assert_no_leaks() {
+    let objects = live_objects();
+    if objects.is_empty() {
+        return;
+    }
+
+    let mut message = format!("{} COM object(s) leaked:", objects.len());
+    for object in &objects {
+        let _ = write!(message, "\n  #{}: {}", object.id, object.type_name);
+        if let Some(backtrace) = &object.backtrace {
+            let _ = write!(message, ", created at:\n{backtrace}");
+        }
+    }
+
+    panic!("{message}");
+}
@@ -2,8 +2,19 @@
 //!
 //! [`ComPtr`] and [`ComRef`] are smart pointers for interacting with COM objects (calling methods,
 //! casting between interfaces, and managing reference counts). The [`Class`] trait can be used for
-//! defining COM classes in Rust, and [`ComWrapper`] is a smart pointer used for instantiating
-//! those classes.
+//! defining COM classes in Rust, and [`ComWrapper`] is a smart pointer used for instantiating those
+//! classes ([`ComWrapperLocal`] is a variant of [`ComWrapper`] for objects which are only used from
+//! a single thread, and [`ComWrapperStatic`] is a variant for objects which can be placed in a
+//! `static` and live for the lifetime of the program). [`AtomicComPtr`] holds an interface pointer
+//! which can be shared between threads and replaced atomically, and [`OnceComPtr`] holds an
+//! interface pointer which is initialized lazily on first use. [`InterfaceMap`] stores pointers to
+//! several different interfaces, keyed by IID. [`ComWeak`] is a weak reference to an object created
+//! by [`ComWrapper`], which can be used to avoid reference cycles. [`OutPtr`] wraps an
+//! out-parameter through which an interface pointer is returned. [`IdentityKey`] makes it possible
+//! to use COM objects as map keys, comparing them by object identity rather than by interface
+//! pointer. [`assert_com_object_valid`] can be used in unit tests to check that a [`Class`] follows
+//! the rules for `query_interface` and reference counting. [`ComResult`] wraps a method's result
+//! code so that failures can be propagated with the `?` operator.
 //!
 //! # Reference counting
 //!
@@ -82,17 +93,158 @@
 //! let ptr = my_obj.to_com_ptr::<IAnotherInterface>().unwrap();
 //! ptr.another_method();
 //! ```
+//!
+//! # Defining interfaces outside of generated bindings
+//!
+//! Interfaces do not have to come from `com-scrape` in order to work with [`ComPtr`], [`ComRef`],
+//! and [`ComWrapper`]. A downstream crate can define additional interfaces (e.g. vendor-specific
+//! extensions which are not part of a set of generated bindings) by hand, as long as it provides
+//! the same set of definitions that `com-scrape` would generate. For an interface `IExtension`
+//! deriving from a generated interface `IBase`, these are:
+//!
+//! 1. A `#[repr(C)]` struct `IExtension` whose only field is a pointer to its virtual table, and a
+//!    `#[repr(C)]` virtual table struct `IExtensionVtbl` whose first field is an `IBaseVtbl`,
+//!    followed by the interface's own methods in declaration order.
+//!
+//! 2. Implementations of [`Unknown`] (which can simply delegate to the corresponding methods of
+//!    `IBase`), [`Interface`], and [`Inherits`] for `IBase` and each of its transitive base
+//!    interfaces.
+//!
+//! 3. A trait `IExtensionTrait` for the interface's own methods, along with a blanket
+//!    implementation of that trait for all [`SmartPtr`] types whose target inherits from
+//!    `IExtension` (see the documentation for [`SmartPtr`]).
+//!
+//! 4. An implementation of [`Construct`] for `IExtension`, which makes it possible to list
+//!    `IExtension` in a [`Class::Interfaces`] tuple. The virtual table for the base interface can
+//!    be obtained from the `make_vtbl` associated function which `com-scrape` generates for each
+//!    interface type:
+//!
+//! ```ignore
+//! impl IExtension {
+//!     pub const fn make_vtbl<C, W, const OFFSET: isize>() -> IExtensionVtbl
+//!     where
+//!         C: IExtensionTrait + Class,
+//!         W: Wrapper<C>,
+//!     {
+//!         unsafe extern "system" fn extension_method<C, W, const OFFSET: isize>(
+//!             this: *mut IExtension,
+//!         ) where
+//!             C: IExtensionTrait + Class,
+//!             W: Wrapper<C>,
+//!         {
+//!             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
+//!             let ptr = W::data_from_header(header_ptr);
+//!             (*ptr).extension_method()
+//!         }
+//!
+//!         IExtensionVtbl {
+//!             base: IBase::make_vtbl::<C, W, OFFSET>(),
+//!             extension_method: extension_method::<C, W, OFFSET>,
+//!         }
+//!     }
+//! }
+//!
+//! unsafe impl<C, W, const OFFSET: isize> Construct<C, W, OFFSET> for IExtension
+//! where
+//!     C: IExtensionTrait + Class,
+//!     W: Wrapper<C>,
+//! {
+//!     const OBJ: IExtension = IExtension {
+//!         vtbl: &Self::make_vtbl::<C, W, OFFSET>(),
+//!     };
+//! }
+//! ```
+//!
+//! The safety requirements for each of these traits are documented on the traits themselves. In
+//! particular, the `OFFSET` parameter of [`Construct`] is the offset of the interface's base class
+//! object within the object header, and method thunks must subtract it from `this` to recover a
+//! pointer to the header before calling [`Wrapper::data_from_header`].
+//!
+//! Rather than writing these definitions by hand, they can be generated from a Rust trait
+//! definition using the `com_interface` attribute macro, which is available with the `macros`
+//! feature enabled:
+//!
+//! ```ignore
+//! #[com_interface(iid = EXTENSION_IID, bases(IBase, IUnknown))]
+//! pub trait IExtension: IBaseTrait {
+//!     unsafe fn extension_method(&self);
+//! }
+//! ```
+//!
+//! # Passing Rust objects between COM objects
+//!
+//! Sometimes two COM objects implemented in the same Rust crate need direct access to each other
+//! (e.g. a plugin's edit controller communicating with its audio processor when both live in the
+//! same process), but only have interface pointers to each other. Rather than defining a private
+//! interface for this purpose, use [`ComWrapper::try_from_com_ptr`] to recover the underlying
+//! [`ComWrapper`]:
+//!
+//! ```ignore
+//! fn connect(&self, other: ComRef<IConnectionPoint>) {
+//!     if let Some(processor) = ComWrapper::<MyProcessor>::try_from_com_ref(other) {
+//!         // `processor` derefs to `MyProcessor`
+//!     }
+//! }
+//! ```
+//!
+//! The identifier used to recognize objects is private to the class and to the binary that
+//! created it, so `try_from_com_ptr` returns `None` for objects of any other type and for objects
+//! created by another copy of the same crate (e.g. one linked into a different plugin binary
+//! which is loaded into the same process), rather than handing out a pointer with an incompatible
+//! layout.
+//!
+//! # `no_std` support
+//!
+//! This crate depends only on `core` and `alloc` when its default `std` feature is disabled.
+//! [`ComPtr`], [`ComRef`], [`ComWrapper`], and the other smart pointers remain available, but
+//! [`OnceComPtr`], [`catch_panic`], and [`set_panic_handler`] require `std`, as do the
+//! `leak-tracking` and `tracing` features. Bindings generated with the `catch_panics` option
+//! enabled call [`catch_panic`], so they also require `std`. Without `std`, objects created by
+//! [`ComWrapperLocal`] are not checked for reference counting operations from other threads.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
 
+mod atomic;
 mod class;
+#[cfg(feature = "leak-tracking")]
+mod leak;
+mod map;
+#[cfg(feature = "std")]
+mod once;
+#[cfg(feature = "std")]
+mod panic;
 mod ptr;
+mod result;
+mod testing;
+mod trace;
 
 #[cfg(test)]
 mod tests;
 
-use std::ffi::c_void;
+use core::ffi::c_void;
 
-pub use class::{Class, ComWrapper, Construct, Header, InterfaceList, MakeHeader, Wrapper};
-pub use ptr::{ComPtr, ComRef, SmartPtr};
+pub use atomic::AtomicComPtr;
+pub use class::{
+    Class, ComWrapper, ComWrapperLocal, ComWrapperStatic, Construct, Header, InterfaceEntry,
+    InterfaceList, MakeHeader, Wrapper,
+};
+#[cfg(feature = "macros")]
+pub use com_scrape_macros::com_interface;
+#[cfg(feature = "leak-tracking")]
+pub use leak::{assert_no_leaks, live_objects, LiveObject};
+pub use map::InterfaceMap;
+#[cfg(feature = "std")]
+pub use once::OnceComPtr;
+#[cfg(feature = "std")]
+pub use panic::{catch_panic, set_panic_handler};
+pub use ptr::{ComPtr, ComRef, ComWeak, IdentityKey, OutPtr, SmartPtr};
+pub use result::{ComResult, ResultCodes};
+pub use testing::assert_com_object_valid;
+pub use trace::{trace_add_ref, trace_query_interface, trace_release};
 
 /// A 16-byte unique identifier for a COM interface.
 pub type Guid = [u8; 16];
@@ -0,0 +1,82 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::any::Any;
+
+use super::{ComPtr, ComRef, Guid, Interface};
+
+/// A collection of [`ComPtr`]s to different interfaces, keyed by IID.
+///
+/// `InterfaceMap` stores at most one pointer per interface type and provides typed access to each
+/// of them. It is useful for caching interface pointers which are discovered over time, such as
+/// extension interfaces queried from a host.
+///
+/// `InterfaceMap` holds a reference to each object it points to, i.e. it will decrement an
+/// object's reference count when the corresponding pointer is removed or when the `InterfaceMap`
+/// is dropped.
+#[derive(Default)]
+pub struct InterfaceMap {
+    ptrs: BTreeMap<Guid, Box<dyn Any>>,
+}
+
+impl InterfaceMap {
+    /// Creates an empty `InterfaceMap`.
+    #[inline]
+    pub fn new() -> InterfaceMap {
+        InterfaceMap {
+            ptrs: BTreeMap::new(),
+        }
+    }
+
+    /// Inserts a pointer to interface `I`, returning the previously stored pointer to `I`, if any.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn insert<I: Interface + 'static>(&mut self, ptr: ComPtr<I>) -> Option<ComPtr<I>> {
+        let previous = self.ptrs.insert(I::IID, Box::new(ptr))?;
+        previous.downcast::<ComPtr<I>>().ok().map(|ptr| *ptr)
+    }
+
+    /// Returns a [`ComRef`] to the stored pointer to interface `I`, if any.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn get<I: Interface + 'static>(&self) -> Option<ComRef<'_, I>> {
+        let ptr = self.ptrs.get(&I::IID)?;
+        ptr.downcast_ref::<ComPtr<I>>().map(ComPtr::as_com_ref)
+    }
+
+    /// Removes the stored pointer to interface `I` and returns it, if any.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn remove<I: Interface + 'static>(&mut self) -> Option<ComPtr<I>> {
+        let ptr = self.ptrs.remove(&I::IID)?;
+        ptr.downcast::<ComPtr<I>>().ok().map(|ptr| *ptr)
+    }
+
+    /// Returns `true` if the map contains a pointer to the interface identified by `iid`.
+    #[inline]
+    pub fn contains(&self, iid: &Guid) -> bool {
+        self.ptrs.contains_key(iid)
+    }
+
+    /// Returns the number of pointers in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ptrs.len()
+    }
+
+    /// Returns `true` if the map contains no pointers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ptrs.is_empty()
+    }
+
+    /// Removes all pointers from the map.
+    ///
+    /// Decrements the reference count of each object that the map points to.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.ptrs.clear();
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::OnceLock;
+
+use super::{ComPtr, ComRef, Interface};
+
+/// An optional [`ComPtr`] which is initialized at most once, on first use.
+///
+/// `OnceComPtr` is intended for interface pointers which are expensive or inconvenient to obtain
+/// up front but which do not change once obtained, such as a host service queried from the host
+/// context. The first call to [`get_or_init`](Self::get_or_init) runs the given closure and stores
+/// its result (including `None`); subsequent calls return the stored pointer without running the
+/// closure again. If multiple threads call [`get_or_init`](Self::get_or_init) concurrently, only
+/// one closure will run, and the other threads will block until it has finished.
+///
+/// `OnceComPtr` holds a reference to the object it points to, i.e. it will decrement the object's
+/// reference count when it is dropped.
+pub struct OnceComPtr<I: Interface> {
+    cell: OnceLock<Option<ComPtr<I>>>,
+}
+
+unsafe impl<I: Interface + Send + Sync> Send for OnceComPtr<I> {}
+unsafe impl<I: Interface + Send + Sync> Sync for OnceComPtr<I> {}
+
+impl<I: Interface> Default for OnceComPtr<I> {
+    #[inline]
+    fn default() -> OnceComPtr<I> {
+        OnceComPtr::new()
+    }
+}
+
+impl<I: Interface> OnceComPtr<I> {
+    /// Creates a new, uninitialized `OnceComPtr`.
+    #[inline]
+    pub const fn new() -> OnceComPtr<I> {
+        OnceComPtr {
+            cell: OnceLock::new(),
+        }
+    }
+
+    /// Returns a [`ComRef`] to the object held by the `OnceComPtr`, or `None` if the `OnceComPtr`
+    /// is uninitialized or was initialized with `None`.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn get(&self) -> Option<ComRef<'_, I>> {
+        self.cell.get()?.as_ref().map(ComPtr::as_com_ref)
+    }
+
+    /// Returns `true` if the `OnceComPtr` has been initialized (possibly with `None`).
+    #[inline]
+    pub fn is_initialized(&self) -> bool {
+        self.cell.get().is_some()
+    }
+
+    /// Returns a [`ComRef`] to the object held by the `OnceComPtr`, initializing it with the
+    /// result of `f` if it is uninitialized.
+    ///
+    /// Does not perform any reference counting operations on the returned pointer. The
+    /// `OnceComPtr` takes ownership of the [`ComPtr`] returned by `f`.
+    #[inline]
+    pub fn get_or_init<F>(&self, f: F) -> Option<ComRef<'_, I>>
+    where
+        F: FnOnce() -> Option<ComPtr<I>>,
+    {
+        self.cell.get_or_init(f).as_ref().map(ComPtr::as_com_ref)
+    }
+
+    /// Initializes the `OnceComPtr` with `ptr` if it is uninitialized.
+    ///
+    /// If the `OnceComPtr` was already initialized, `ptr` is returned back to the caller as an
+    /// error.
+    #[inline]
+    pub fn set(&self, ptr: Option<ComPtr<I>>) -> Result<(), Option<ComPtr<I>>> {
+        self.cell.set(ptr)
+    }
+
+    /// Consumes the `OnceComPtr`, returning the pointer it holds.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn into_inner(self) -> Option<ComPtr<I>> {
+        self.cell.into_inner().flatten()
+    }
+}
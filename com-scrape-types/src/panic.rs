@@ -0,0 +1,51 @@
+use std::any::Any;
+use std::boxed::Box;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::RwLock;
+
+type PanicHandler = Box<dyn Fn(&(dyn Any + Send)) + Send + Sync>;
+
+static PANIC_HANDLER: RwLock<Option<PanicHandler>> = RwLock::new(None);
+
+/// Installs a function to be called with the payload of each panic caught by [`catch_panic`],
+/// replacing any previously installed function.
+///
+/// This can be used to log panics which occur in interface method implementations, or to put the
+/// object in a safe state after one. The payload is typically a `&'static str` or a `String`
+/// containing the panic message.
+///
+/// Panics which occur inside the handler itself are ignored.
+pub fn set_panic_handler<F>(handler: F)
+where
+    F: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+{
+    let mut guard = PANIC_HANDLER.write().unwrap_or_else(|err| err.into_inner());
+    *guard = Some(Box::new(handler));
+}
+
+/// Calls `f`, catching any panic which unwinds out of it.
+///
+/// If `f` panics, the handler installed with [`set_panic_handler`] (if any) is called with the
+/// panic's payload, and the value returned by `on_panic` is returned in place of `f`'s result.
+///
+/// A panic which unwinds out of an `extern "system"` function called from C++ aborts the process,
+/// so when the `catch_panics` option is enabled, the virtual table entries generated by
+/// `com-scrape` wrap each method call in `catch_panic`. Panics cannot be caught when compiling
+/// with `panic = "abort"`.
+pub fn catch_panic<R, F, G>(f: F, on_panic: G) -> R
+where
+    F: FnOnce() -> R,
+    G: FnOnce() -> R,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let guard = PANIC_HANDLER.read().unwrap_or_else(|err| err.into_inner());
+            if let Some(handler) = &*guard {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(&*payload)));
+            }
+
+            on_panic()
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use std::marker::PhantomData;
-use std::mem;
-use std::ptr::NonNull;
+use alloc::format;
+use core::ffi::c_void;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
 
-use super::{Inherits, Interface};
+use super::{Guid, Inherits, Interface};
 
 /// Trait for types that represent a smart pointer to a COM object.
 ///
@@ -43,6 +47,10 @@ pub trait SmartPtr {
 /// A `ComRef` can be created safely from a [`ComPtr`] via [`ComPtr::as_com_ref`], or from a
 /// [`ComWrapper`][crate::ComWrapper] via [`ComWrapper::as_com_ref`][crate::ComWrapper::as_com_ref].
 /// It can also be created unsafely via [`ComRef::from_raw`].
+///
+/// Like [`ComPtr`], `ComRef<'a, I>` is guaranteed to have the same size and layout as a non-null
+/// `*mut I`, and `Option<ComRef<'a, I>>` is guaranteed to have the same size as `*mut I`.
+#[repr(transparent)]
 pub struct ComRef<'a, I: Interface> {
     ptr: NonNull<I>,
     _marker: PhantomData<&'a I>,
@@ -69,6 +77,12 @@ impl<'a, I: Interface> Clone for ComRef<'a, I> {
     }
 }
 
+impl<'a, I: Interface> fmt::Debug for ComRef<'a, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_interface_ptr("ComRef", self.ptr, f)
+    }
+}
+
 impl<'a, I: Interface> ComRef<'a, I> {
     /// Gets the wrapped interface pointer.
     ///
@@ -78,6 +92,14 @@ impl<'a, I: Interface> ComRef<'a, I> {
         self.ptr.as_ptr() as *mut I
     }
 
+    /// Gets the wrapped interface pointer as a [`NonNull`].
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn as_non_null(&self) -> NonNull<I> {
+        self.ptr
+    }
+
     /// Creates a `ComRef` from a raw interface pointer if the pointer is non-null.
     ///
     /// Does not perform any reference counting operations.
@@ -155,6 +177,35 @@ impl<'a, I: Interface> ComRef<'a, I> {
             }
         }
     }
+
+    /// Returns `true` if `self` and `other` point to the same COM object.
+    ///
+    /// Two interface pointers to the same object may have different addresses if they point to
+    /// different interfaces, so comparing them by address is not sufficient. Per the rules of COM,
+    /// querying any of an object's interfaces for the root interface `U` (e.g. `IUnknown`) always
+    /// returns the same pointer, and `identity_eq` compares the results of these queries. Returns
+    /// `false` if either query fails.
+    #[inline]
+    pub fn identity_eq<U: Interface, J: Interface>(&self, other: ComRef<J>) -> bool {
+        match (self.cast::<U>(), other.cast::<U>()) {
+            (Some(a), Some(b)) => a.as_ptr() == b.as_ptr(),
+            _ => false,
+        }
+    }
+}
+
+/// Borrows a [`ComPtr`] to a derived interface as a [`ComRef`] to a base interface.
+///
+/// Does not perform any reference counting operations.
+impl<'a, I, J> From<&'a ComPtr<I>> for ComRef<'a, J>
+where
+    I: Inherits<J>,
+    J: Interface,
+{
+    #[inline]
+    fn from(ptr: &'a ComPtr<I>) -> ComRef<'a, J> {
+        ptr.as_com_ref().upcast()
+    }
 }
 
 /// An owning smart pointer to a COM object.
@@ -168,6 +219,11 @@ impl<'a, I: Interface> ComRef<'a, I> {
 /// A `ComPtr` can be created safely from a [`ComRef`] via [`ComRef::to_com_ptr`], or from a
 /// [`ComWrapper`][crate::ComWrapper] via [`ComWrapper::to_com_ptr`][crate::ComWrapper::to_com_ptr].
 /// It can also be created unsafely via [`ComPtr::from_raw`].
+///
+/// `ComPtr<I>` is guaranteed to have the same size and layout as a non-null `*mut I`, and
+/// `Option<ComPtr<I>>` is guaranteed to have the same size as `*mut I`, with `None` represented as
+/// a null pointer.
+#[repr(transparent)]
 pub struct ComPtr<I: Interface> {
     ptr: NonNull<I>,
 }
@@ -201,6 +257,26 @@ impl<I: Interface> Drop for ComPtr<I> {
     }
 }
 
+/// Upgrades a [`ComRef`] to a derived interface to a [`ComPtr`] to a base interface.
+///
+/// Increments the reference count of the object that the [`ComRef`] points to.
+impl<'a, I, J> From<ComRef<'a, I>> for ComPtr<J>
+where
+    I: Inherits<J>,
+    J: Interface,
+{
+    #[inline]
+    fn from(ptr: ComRef<'a, I>) -> ComPtr<J> {
+        ptr.upcast().to_com_ptr()
+    }
+}
+
+impl<I: Interface> fmt::Debug for ComPtr<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_interface_ptr("ComPtr", self.ptr, f)
+    }
+}
+
 impl<I: Interface> ComPtr<I> {
     /// Gets the wrapped interface pointer.
     ///
@@ -210,6 +286,14 @@ impl<I: Interface> ComPtr<I> {
         self.ptr.as_ptr() as *mut I
     }
 
+    /// Gets the wrapped interface pointer as a [`NonNull`].
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn as_non_null(&self) -> NonNull<I> {
+        self.ptr
+    }
+
     /// Creates a `ComPtr` from a raw interface pointer if the pointer is non-null.
     ///
     /// When the resulting `ComPtr` is dropped, the reference count of the object it points to will
@@ -269,7 +353,14 @@ impl<I: Interface> ComPtr<I> {
         unsafe { ComRef::from_raw_unchecked(self.ptr.as_ptr()) }
     }
 
+    /// Casts the `ComPtr` from a derived interface to a base interface.
+    ///
     /// Does not perform any reference counting operations.
+    ///
+    /// Note that there is no corresponding `From<ComPtr<I>>` implementation for `ComPtr<J>`, since
+    /// it would overlap with the standard library's reflexive `From<T>` implementation for `T`.
+    /// Use `upcast` instead, or pass a [`ComRef`] to the base interface (which can be obtained
+    /// from a `&ComPtr` to a derived interface via [`From`]).
     #[inline]
     pub fn upcast<J: Interface>(self) -> ComPtr<J>
     where
@@ -292,4 +383,307 @@ impl<I: Interface> ComPtr<I> {
             }
         }
     }
+
+    /// Returns `true` if `self` and `other` point to the same COM object.
+    ///
+    /// See [`ComRef::identity_eq`] for details.
+    #[inline]
+    pub fn identity_eq<U: Interface, J: Interface>(&self, other: &ComPtr<J>) -> bool {
+        self.as_com_ref().identity_eq::<U, J>(other.as_com_ref())
+    }
+}
+
+/// A key which identifies a COM object by its identity rather than by the address of a particular
+/// interface pointer.
+///
+/// `IdentityKey<U>` holds a pointer to the object's root interface `U` (e.g. `IUnknown`), obtained
+/// by querying for `U`. Since querying any of an object's interfaces for `U` returns the same
+/// pointer, two `IdentityKey`s are equal if and only if they refer to the same object, which makes
+/// it possible to use COM objects as keys in a `HashMap` or `HashSet`:
+///
+/// ```ignore
+/// let mut connections = HashMap::new();
+/// connections.insert(IdentityKey::<FUnknown>::new(processor.as_com_ref()).unwrap(), state);
+///
+/// // Looking up the same object via a different interface finds the same entry
+/// let key = IdentityKey::<FUnknown>::new(connection_point.as_com_ref()).unwrap();
+/// assert!(connections.contains_key(&key));
+/// ```
+///
+/// The key holds a reference to the object, so the object's address cannot be reused by another
+/// object while the key exists.
+#[derive(Clone)]
+pub struct IdentityKey<U: Interface> {
+    ptr: ComPtr<U>,
+}
+
+impl<U: Interface> IdentityKey<U> {
+    /// Creates an `IdentityKey` for the object that `ptr` points to, or returns `None` if the
+    /// object does not implement `U`.
+    ///
+    /// Increments the reference count of the object.
+    #[inline]
+    pub fn new<I: Interface>(ptr: ComRef<I>) -> Option<IdentityKey<U>> {
+        ptr.cast::<U>().map(|ptr| IdentityKey { ptr })
+    }
+
+    /// Returns a [`ComPtr`] to the object's root interface.
+    #[inline]
+    pub fn as_com_ptr(&self) -> &ComPtr<U> {
+        &self.ptr
+    }
+}
+
+impl<U: Interface> PartialEq for IdentityKey<U> {
+    #[inline]
+    fn eq(&self, other: &IdentityKey<U>) -> bool {
+        self.ptr.as_ptr() == other.ptr.as_ptr()
+    }
+}
+
+impl<U: Interface> Eq for IdentityKey<U> {}
+
+impl<U: Interface> Hash for IdentityKey<U> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ptr.as_ptr().hash(state);
+    }
+}
+
+impl<U: Interface> fmt::Debug for IdentityKey<U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IdentityKey").field(&self.ptr).finish()
+    }
+}
+
+// Formats a GUID in the canonical 8-4-4-4-12 form, matching the IIDs listed in the index generated
+// by vst3-bindgen. On Windows, GUIDs use the COM layout, in which the first three groups are
+// stored as little-endian integers; on other platforms, all of the bytes are stored in order.
+pub(crate) struct GuidFmt<'a>(pub(crate) &'a Guid);
+
+impl<'a> fmt::Debug for GuidFmt<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = *self.0;
+        if cfg!(target_os = "windows") {
+            bytes[0..4].reverse();
+            bytes[4..6].reverse();
+            bytes[6..8].reverse();
+        }
+
+        write!(f, "{{")?;
+        for (i, byte) in bytes.iter().enumerate() {
+            if let 4 | 6 | 8 | 10 = i {
+                write!(f, "-")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+// Shared Debug implementation for ComPtr and ComRef. With the debug-ref-count feature enabled,
+// includes the object's current reference count, obtained by calling add_ref followed by release.
+fn fmt_interface_ptr<I: Interface>(
+    kind: &str,
+    ptr: NonNull<I>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    let name = format!("{kind}<{}>", core::any::type_name::<I>());
+    let mut debug = f.debug_struct(&name);
+    debug.field("ptr", &ptr.as_ptr());
+    debug.field("iid", &GuidFmt(&I::IID));
+
+    #[cfg(feature = "debug-ref-count")]
+    {
+        let ref_count = unsafe {
+            I::add_ref(ptr.as_ptr());
+            I::release(ptr.as_ptr())
+        };
+        debug.field("ref_count", &ref_count);
+    }
+
+    debug.finish()
+}
+
+/// A COM out-parameter through which an interface pointer is returned to the caller.
+///
+/// Methods such as `query_interface` or `IPluginFactory::createInstance` return objects by
+/// writing an interface pointer to a location provided by the caller, with ownership of one
+/// reference passing to the caller. `OutPtr<I>` wraps such a location and encapsulates the pointer
+/// writes and the transfer of ownership:
+///
+/// ```ignore
+/// unsafe fn createInstance(
+///     &self,
+///     cid: FIDString,
+///     _iid: FIDString,
+///     obj: *mut *mut c_void,
+/// ) -> tresult {
+///     let Some(obj) = OutPtr::<FUnknown>::from_raw_void(obj) else {
+///         return kInvalidArgument;
+///     };
+///
+///     if let Some(instance) = self.create(cid) {
+///         obj.write(instance);
+///         kResultOk
+///     } else {
+///         obj.write_null();
+///         kInvalidArgument
+///     }
+/// }
+/// ```
+///
+/// The previous contents of the location are never read or released, since out-parameters are not
+/// required to be initialized by the caller.
+pub struct OutPtr<'a, I: Interface> {
+    ptr: NonNull<*mut I>,
+    _marker: PhantomData<&'a mut *mut I>,
+}
+
+impl<'a, I: Interface> OutPtr<'a, I> {
+    /// Creates an `OutPtr` from a raw out-parameter if the pointer is non-null.
+    ///
+    /// # Safety
+    ///
+    /// If `ptr` is non-null, it must be valid for writes of a `*mut I` for the lifetime `'a`.
+    #[inline]
+    pub unsafe fn from_raw(ptr: *mut *mut I) -> Option<OutPtr<'a, I>> {
+        NonNull::new(ptr).map(|ptr| OutPtr {
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates an `OutPtr` from an untyped raw out-parameter (as used by e.g. `query_interface`)
+    /// if the pointer is non-null.
+    ///
+    /// # Safety
+    ///
+    /// If `ptr` is non-null, it must be valid for writes of a `*mut c_void` for the lifetime `'a`,
+    /// and the caller must expect an interface pointer of type `I` (or of an interface which `I`
+    /// derives from) to be written to it.
+    #[inline]
+    pub unsafe fn from_raw_void(ptr: *mut *mut c_void) -> Option<OutPtr<'a, I>> {
+        Self::from_raw(ptr as *mut *mut I)
+    }
+
+    /// Writes an interface pointer to the out-parameter, transferring ownership of the reference
+    /// held by `ptr` to the caller.
+    ///
+    /// Does not perform any reference counting operations.
+    #[inline]
+    pub fn write<J>(self, ptr: ComPtr<J>)
+    where
+        J: Inherits<I>,
+    {
+        unsafe {
+            *self.ptr.as_ptr() = ptr.upcast::<I>().into_raw();
+        }
+    }
+
+    /// Writes a null pointer to the out-parameter.
+    ///
+    /// COM methods which fail should generally do this, since callers may otherwise treat the
+    /// uninitialized contents of the out-parameter as an interface pointer.
+    #[inline]
+    pub fn write_null(self) {
+        unsafe {
+            *self.ptr.as_ptr() = core::ptr::null_mut();
+        }
+    }
+
+    /// Writes `Some` interface pointer, or a null pointer for `None`, to the out-parameter.
+    ///
+    /// Equivalent to calling [`write`](Self::write) or [`write_null`](Self::write_null).
+    #[inline]
+    pub fn write_option<J>(self, ptr: Option<ComPtr<J>>)
+    where
+        J: Inherits<I>,
+    {
+        match ptr {
+            Some(ptr) => self.write(ptr),
+            None => self.write_null(),
+        }
+    }
+}
+
+// Operations for managing the weak reference count of the object that a ComWeak points to, which
+// depend on how the object was allocated. Each takes the object pointer stored in the ComWeak.
+#[derive(Copy, Clone)]
+pub(crate) struct WeakOps {
+    // Increments the object's strong reference count and returns true, unless the object has
+    // already been destroyed.
+    pub(crate) upgrade: unsafe fn(*const c_void) -> bool,
+    pub(crate) clone: unsafe fn(*const c_void),
+    pub(crate) release: unsafe fn(*const c_void),
+}
+
+/// A weak reference to a COM object implemented in Rust.
+///
+/// A `ComWeak<I>` does not keep the object it points to alive. Instead, it can be upgraded to a
+/// [`ComPtr<I>`] via [`upgrade`](Self::upgrade) as long as the object still exists. This makes it
+/// possible for objects to hold back-references to each other (e.g. from an edit controller to
+/// its audio processor) without creating reference cycles which would prevent either object from
+/// being destroyed.
+///
+/// Since the COM reference counting protocol has no notion of weak references, a `ComWeak` can
+/// only be created for objects allocated by a [`ComWrapper`][crate::ComWrapper], via
+/// [`ComWrapper::downgrade`][crate::ComWrapper::downgrade]. A `ComWeak` keeps the memory for the
+/// object allocated (but not the object itself) until it is dropped.
+pub struct ComWeak<I: Interface> {
+    ptr: NonNull<I>,
+    object: *const c_void,
+    ops: WeakOps,
+}
+
+impl<I: Interface> Clone for ComWeak<I> {
+    #[inline]
+    fn clone(&self) -> ComWeak<I> {
+        unsafe {
+            (self.ops.clone)(self.object);
+        }
+
+        ComWeak {
+            ptr: self.ptr,
+            object: self.object,
+            ops: self.ops,
+        }
+    }
+}
+
+impl<I: Interface> Drop for ComWeak<I> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            (self.ops.release)(self.object);
+        }
+    }
+}
+
+impl<I: Interface> ComWeak<I> {
+    // Takes ownership of one weak reference to `object`. `ptr` must point to an interface within
+    // `object`.
+    #[inline]
+    pub(crate) unsafe fn from_raw_parts(
+        ptr: NonNull<I>,
+        object: *const c_void,
+        ops: WeakOps,
+    ) -> ComWeak<I> {
+        ComWeak { ptr, object, ops }
+    }
+
+    /// Attempts to obtain a [`ComPtr`] to the object, returning `None` if the object has already
+    /// been destroyed.
+    ///
+    /// If a [`ComPtr`] is returned, the object's reference count will be incremented.
+    #[inline]
+    pub fn upgrade(&self) -> Option<ComPtr<I>> {
+        unsafe {
+            if (self.ops.upgrade)(self.object) {
+                Some(ComPtr::from_raw_unchecked(self.ptr.as_ptr()))
+            } else {
+                None
+            }
+        }
+    }
 }
@@ -0,0 +1,130 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+/// The set of result codes returned by the methods of a particular COM API.
+///
+/// Result codes are plain integers, and their values are determined by the API rather than by
+/// COM itself (for instance, the VST 3 API only uses the standard `HRESULT` values on Windows).
+/// This trait is implemented by a marker type in the bindings for an API, and supplies the
+/// values of the constants on [`ComResult`]. The standard `HRESULT` code corresponding to each
+/// constant is given in its documentation.
+pub trait ResultCodes {
+    /// The integer type of result codes (e.g. `HRESULT`).
+    type Code: Copy + Eq + Hash + fmt::Debug;
+
+    /// The operation succeeded (`S_OK`).
+    const OK: Self::Code;
+    /// The operation succeeded, with a negative outcome (`S_FALSE`).
+    const FALSE: Self::Code;
+    /// The requested interface is not implemented (`E_NOINTERFACE`).
+    const NO_INTERFACE: Self::Code;
+    /// An argument is invalid (`E_INVALIDARG`).
+    const INVALID_ARGUMENT: Self::Code;
+    /// The method is not implemented (`E_NOTIMPL`).
+    const NOT_IMPLEMENTED: Self::Code;
+    /// An unspecified error occurred (`E_FAIL`).
+    const INTERNAL_ERROR: Self::Code;
+    /// The object is not in a state in which the method can be called (`E_UNEXPECTED`).
+    const NOT_INITIALIZED: Self::Code;
+    /// Memory could not be allocated (`E_OUTOFMEMORY`).
+    const OUT_OF_MEMORY: Self::Code;
+}
+
+/// A result code returned by a COM method, using the code set `S`.
+///
+/// Methods in generated bindings return raw result codes. `ComResult` makes it possible to write
+/// the body of an interface method in terms of `Result<(), ComResult<S>>`, using the `?` operator
+/// to propagate failures, and then convert the result back to a raw code at the boundary:
+///
+/// ```ignore
+/// unsafe fn set_active(&self, state: bool) -> HRESULT {
+///     ComResult::from(self.try_set_active(state)).0
+/// }
+///
+/// fn try_set_active(&self, active: bool) -> Result<(), ComResult<HResultCodes>> {
+///     ComResult::new(self.host.notify(active)).into_result()?;
+///     self.allocate_buffers().ok_or(ComResult::OUT_OF_MEMORY)?;
+///     Ok(())
+/// }
+/// ```
+///
+/// [`into_result`](Self::into_result) treats every code other than [`OK`](Self::OK) as a
+/// failure, including [`FALSE`](Self::FALSE). Methods which return `FALSE` as a non-error result
+/// should compare against it directly.
+#[repr(transparent)]
+pub struct ComResult<S: ResultCodes>(pub S::Code);
+
+impl<S: ResultCodes> ComResult<S> {
+    pub const OK: Self = ComResult(S::OK);
+    pub const FALSE: Self = ComResult(S::FALSE);
+    pub const NO_INTERFACE: Self = ComResult(S::NO_INTERFACE);
+    pub const INVALID_ARGUMENT: Self = ComResult(S::INVALID_ARGUMENT);
+    pub const NOT_IMPLEMENTED: Self = ComResult(S::NOT_IMPLEMENTED);
+    pub const INTERNAL_ERROR: Self = ComResult(S::INTERNAL_ERROR);
+    pub const NOT_INITIALIZED: Self = ComResult(S::NOT_INITIALIZED);
+    pub const OUT_OF_MEMORY: Self = ComResult(S::OUT_OF_MEMORY);
+
+    /// Wraps a raw result code.
+    #[inline]
+    pub const fn new(code: S::Code) -> ComResult<S> {
+        ComResult(code)
+    }
+
+    /// Returns `true` if the value is [`OK`](Self::OK).
+    #[inline]
+    pub fn is_ok(self) -> bool {
+        self.0 == S::OK
+    }
+
+    /// Converts the value to `Ok(())` if it is [`OK`](Self::OK), or to `Err(self)` otherwise.
+    #[inline]
+    pub fn into_result(self) -> Result<(), ComResult<S>> {
+        if self.is_ok() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// Converts `Ok(())` to [`ComResult::OK`], and `Err(result)` to `result`.
+impl<S: ResultCodes> From<Result<(), ComResult<S>>> for ComResult<S> {
+    #[inline]
+    fn from(value: Result<(), ComResult<S>>) -> ComResult<S> {
+        match value {
+            Ok(()) => ComResult::OK,
+            Err(result) => result,
+        }
+    }
+}
+
+impl<S: ResultCodes> Copy for ComResult<S> {}
+
+impl<S: ResultCodes> Clone for ComResult<S> {
+    #[inline]
+    fn clone(&self) -> ComResult<S> {
+        *self
+    }
+}
+
+impl<S: ResultCodes> PartialEq for ComResult<S> {
+    #[inline]
+    fn eq(&self, other: &ComResult<S>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<S: ResultCodes> Eq for ComResult<S> {}
+
+impl<S: ResultCodes> Hash for ComResult<S> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl<S: ResultCodes> fmt::Debug for ComResult<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ComResult").field(&self.0).finish()
+    }
+}
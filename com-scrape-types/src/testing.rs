@@ -0,0 +1,121 @@
+use alloc::vec;
+
+use super::{Class, ComWrapper, Guid, Interface, InterfaceList};
+
+/// Checks that an object created by a [`ComWrapper`] follows the rules for `query_interface` and
+/// reference counting, and panics if it does not.
+///
+/// `U` is the root interface from which all COM interfaces derive (`IUnknown` or an equivalent,
+/// such as `FUnknown` in the VST 3 API). Each interface in `C`'s interface list must derive from
+/// `U`. The following properties are checked for `U` and each interface in the list:
+///
+/// 1. Querying for the interface succeeds, regardless of which interface pointer is queried.
+///
+/// 2. Querying for the interface always returns the same pointer, and querying that pointer for
+///    `U` returns the same pointer as querying any other interface pointer for `U` (i.e. the
+///    object has a stable identity).
+///
+/// 3. If interface `B` can be obtained from interface `A`, then interface `A` can be obtained
+///    from the resulting pointer to `B`.
+///
+/// 4. Querying for an IID which is not implemented by the object fails.
+///
+/// 5. Every successful query increments the object's reference count by exactly one, so that
+///    releasing each returned pointer restores the original count.
+///
+/// This is intended to be called from a unit test for a class:
+///
+/// ```ignore
+/// #[test]
+/// fn my_class_is_valid() {
+///     assert_com_object_valid::<IUnknown, _>(&ComWrapper::new(MyClass::new()));
+/// }
+/// ```
+pub fn assert_com_object_valid<U: Interface, C: Class>(obj: &ComWrapper<C>) {
+    let root = obj
+        .as_com_ref::<U>()
+        .expect("object does not implement the root interface");
+    let root_ptr = root.as_ptr();
+
+    let mut iids = vec![U::IID];
+    for (iid, inherits) in C::Interfaces::INTERFACES {
+        assert!(
+            inherits(&U::IID),
+            "interface {iid:?} does not derive from the root interface"
+        );
+        iids.push(*iid);
+    }
+
+    unsafe {
+        let count = |ptr: *mut U| {
+            U::add_ref(ptr);
+            U::release(ptr)
+        };
+
+        let query = |ptr: *mut U, iid: &Guid| -> *mut U {
+            let result = U::query_interface(ptr, iid)
+                .unwrap_or_else(|| panic!("query for implemented interface {iid:?} failed"));
+            result as *mut U
+        };
+
+        let initial_count = count(root_ptr);
+
+        for iid in &iids {
+            let ptr = query(root_ptr, iid);
+            assert_eq!(
+                count(ptr),
+                initial_count + 1,
+                "query for interface {iid:?} did not increment the reference count by one"
+            );
+
+            let again = query(ptr, iid);
+            assert_eq!(
+                again, ptr,
+                "queries for interface {iid:?} returned different pointers"
+            );
+            U::release(again);
+
+            let identity = query(ptr, &U::IID);
+            assert_eq!(
+                identity, root_ptr,
+                "querying interface {iid:?} for the root interface returned a different pointer"
+            );
+            U::release(identity);
+
+            for other in &iids {
+                let other_ptr = query(ptr, other);
+                let direct = query(root_ptr, other);
+                assert_eq!(
+                    other_ptr, direct,
+                    "querying interface {iid:?} for interface {other:?} returned a different pointer"
+                );
+                U::release(direct);
+
+                let back = query(other_ptr, iid);
+                assert_eq!(
+                    back, ptr,
+                    "querying interface {other:?} for interface {iid:?} returned a different pointer"
+                );
+                U::release(back);
+                U::release(other_ptr);
+            }
+
+            U::release(ptr);
+        }
+
+        let mut unknown = [0xFF; 16];
+        while C::Interfaces::query(&unknown).is_some() {
+            unknown[0] -= 1;
+        }
+        if let Some(ptr) = U::query_interface(root_ptr, &unknown) {
+            U::release(ptr as *mut U);
+            panic!("query for unimplemented interface {unknown:?} succeeded");
+        }
+
+        assert_eq!(
+            count(root_ptr),
+            initial_count,
+            "reference count was not restored after releasing all queried pointers"
+        );
+    }
+}
@@ -1,8 +1,11 @@
-use std::cell::Cell;
+use std::boxed::Box;
+use std::cell::{Cell, RefCell};
 use std::ffi::{c_long, c_ulong, c_void};
-use std::ptr;
 use std::rc::Rc;
+use std::vec::Vec;
+use std::{format, ptr};
 
+use crate::ptr::GuidFmt;
 use crate::*;
 
 #[repr(C)]
@@ -68,12 +71,24 @@ impl IUnknown {
             W: Wrapper<C>,
         {
             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
-            if let Some(result) = C::Interfaces::query(&*(_iid as *const Guid)) {
-                let ptr = W::data_from_header(header_ptr);
+            let ptr = W::data_from_header(header_ptr);
+            let iid = &*(_iid as *const Guid);
+
+            let result = if let Some(wrapper_ptr) = W::query_wrapper(ptr, iid) {
+                Some(wrapper_ptr)
+            } else if let Some(result) = W::query_outer(ptr, iid) {
+                result
+            } else if let Some(offset) = C::Interfaces::query(iid) {
                 W::add_ref(ptr);
+                Some((header_ptr as *mut u8).offset(offset) as *mut c_void)
+            } else {
+                None
+            };
 
-                *obj = (header_ptr as *mut u8).offset(result) as *mut c_void;
+            trace_query_interface::<C>(this as *const c_void, iid, result.is_some());
 
+            if let Some(result) = result {
+                *obj = result;
                 0
             } else {
                 1
@@ -87,7 +102,9 @@ impl IUnknown {
         {
             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
             let ptr = W::data_from_header(header_ptr);
-            W::add_ref(ptr) as c_ulong
+            let count = W::add_ref(ptr);
+            trace_add_ref::<C>(this as *const c_void, count);
+            count as c_ulong
         }
 
         unsafe extern "system" fn release<C, W, const OFFSET: isize>(this: *mut IUnknown) -> c_ulong
@@ -97,7 +114,9 @@ impl IUnknown {
         {
             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
             let ptr = W::data_from_header(header_ptr);
-            W::release(ptr) as c_ulong
+            let count = W::release(ptr);
+            trace_release::<C>(this as *const c_void, count);
+            count as c_ulong
         }
 
         IUnknownVtbl {
@@ -296,6 +315,97 @@ where
     };
 }
 
+#[repr(C)]
+struct IDerivedInterface {
+    vtbl: *const IDerivedInterfaceVtbl,
+}
+
+#[repr(C)]
+struct IDerivedInterfaceVtbl {
+    base: IMyInterfaceVtbl,
+    derived_method: unsafe extern "system" fn(this: *mut IDerivedInterface) -> u32,
+}
+
+trait IDerivedInterfaceTrait: IMyInterfaceTrait {
+    fn derived_method(&self) -> u32;
+}
+
+impl<P> IDerivedInterfaceTrait for P
+where
+    P: SmartPtr,
+    P::Target: Inherits<IDerivedInterface>,
+    P::Target: Inherits<IMyInterface>,
+{
+    fn derived_method(&self) -> u32 {
+        unsafe {
+            let ptr = self.ptr() as *mut IDerivedInterface;
+            ((*(*ptr).vtbl).derived_method)(ptr)
+        }
+    }
+}
+
+impl Unknown for IDerivedInterface {
+    unsafe fn query_interface(this: *mut Self, iid: &Guid) -> Option<*mut c_void> {
+        IMyInterface::query_interface(this as *mut IMyInterface, iid)
+    }
+
+    unsafe fn add_ref(this: *mut Self) -> usize {
+        IMyInterface::add_ref(this as *mut IMyInterface)
+    }
+
+    unsafe fn release(this: *mut Self) -> usize {
+        IMyInterface::release(this as *mut IMyInterface)
+    }
+}
+
+unsafe impl Interface for IDerivedInterface {
+    type Vtbl = IDerivedInterfaceVtbl;
+
+    const IID: Guid = *b"dddddddddddddddd";
+
+    fn inherits(iid: &Guid) -> bool {
+        iid == &Self::IID || IMyInterface::inherits(iid)
+    }
+}
+
+unsafe impl Inherits<IMyInterface> for IDerivedInterface {}
+unsafe impl Inherits<IUnknown> for IDerivedInterface {}
+
+impl IDerivedInterface {
+    pub const fn make_vtbl<C, W, const OFFSET: isize>() -> IDerivedInterfaceVtbl
+    where
+        C: IDerivedInterfaceTrait + Class,
+        W: Wrapper<C>,
+    {
+        unsafe extern "system" fn derived_method<C, W, const OFFSET: isize>(
+            this: *mut IDerivedInterface,
+        ) -> u32
+        where
+            C: IDerivedInterfaceTrait + Class,
+            W: Wrapper<C>,
+        {
+            let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
+            let ptr = W::data_from_header(header_ptr);
+            (*ptr).derived_method()
+        }
+
+        IDerivedInterfaceVtbl {
+            base: IMyInterface::make_vtbl::<C, W, OFFSET>(),
+            derived_method: derived_method::<C, W, OFFSET>,
+        }
+    }
+}
+
+unsafe impl<C, W, const OFFSET: isize> Construct<C, W, OFFSET> for IDerivedInterface
+where
+    C: IDerivedInterfaceTrait + Class,
+    W: Wrapper<C>,
+{
+    const OBJ: IDerivedInterface = IDerivedInterface {
+        vtbl: &Self::make_vtbl::<C, W, OFFSET>(),
+    };
+}
+
 #[repr(C)]
 struct MyClass {
     my_interface: IMyInterface,
@@ -489,3 +599,995 @@ fn com_wrapper() {
     drop(com_ptr_4);
     assert_eq!(dropped.get(), true);
 }
+
+struct MyClass3 {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+impl Class for MyClass3 {
+    type Interfaces = (IOtherInterface, IDerivedInterface);
+}
+
+impl IMyInterfaceTrait for MyClass3 {
+    fn my_method(&self) -> u32 {
+        self.x
+    }
+}
+
+impl IOtherInterfaceTrait for MyClass3 {
+    fn other_method(&self) -> u32 {
+        self.y
+    }
+}
+
+impl IDerivedInterfaceTrait for MyClass3 {
+    fn derived_method(&self) -> u32 {
+        self.z
+    }
+}
+
+#[test]
+fn derived_interface() {
+    let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    let derived = obj.to_com_ptr::<IDerivedInterface>().unwrap();
+    assert_eq!(derived.my_method(), 1);
+    assert_eq!(derived.derived_method(), 3);
+
+    let my_interface = obj.to_com_ptr::<IMyInterface>().unwrap();
+    assert_eq!(my_interface.my_method(), 1);
+    assert_eq!(
+        my_interface.as_ptr() as *mut u8,
+        derived.as_ptr() as *mut u8
+    );
+
+    let other = my_interface.cast::<IOtherInterface>().unwrap();
+    assert_eq!(other.other_method(), 2);
+
+    let derived = other.cast::<IDerivedInterface>().unwrap();
+    assert_eq!(derived.upcast::<IMyInterface>().my_method(), 1);
+}
+
+#[test]
+fn upcast_conversions() {
+    let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    fn call_my_method(ptr: ComRef<IMyInterface>) -> u32 {
+        ptr.my_method()
+    }
+
+    let derived = obj.to_com_ptr::<IDerivedInterface>().unwrap();
+    assert_eq!(call_my_method((&derived).into()), 1);
+
+    let base: ComPtr<IMyInterface> = derived.as_com_ref().into();
+    assert_eq!(base.my_method(), 1);
+    assert_eq!(base.as_ptr() as *mut u8, derived.as_ptr() as *mut u8);
+
+    let unknown = base.upcast::<IUnknown>();
+    assert!(unknown.cast::<IDerivedInterface>().is_some());
+}
+
+#[test]
+fn pointer_size() {
+    use std::mem::size_of;
+
+    assert_eq!(size_of::<ComPtr<IUnknown>>(), size_of::<*mut IUnknown>());
+    assert_eq!(
+        size_of::<Option<ComPtr<IUnknown>>>(),
+        size_of::<*mut IUnknown>()
+    );
+    assert_eq!(size_of::<ComRef<IUnknown>>(), size_of::<*mut IUnknown>());
+    assert_eq!(
+        size_of::<Option<ComRef<IUnknown>>>(),
+        size_of::<*mut IUnknown>()
+    );
+
+    let obj = MyClass::new();
+    let com_ptr = unsafe { ComPtr::from_raw(&obj as *const MyClass as *mut IUnknown) }.unwrap();
+    assert_eq!(com_ptr.as_non_null().as_ptr(), com_ptr.as_ptr());
+    assert_eq!(com_ptr.as_com_ref().as_non_null(), com_ptr.as_non_null());
+}
+
+#[test]
+fn atomic_com_ptr() {
+    let obj = MyClass::new();
+    let com_ptr = unsafe { ComPtr::from_raw(&obj as *const MyClass as *mut IUnknown) }.unwrap();
+
+    let atomic = AtomicComPtr::new(None);
+    assert!(atomic.load().is_none());
+
+    atomic.store(Some(com_ptr));
+    assert_eq!(obj.count.get(), 1);
+
+    let loaded = atomic.load().unwrap();
+    assert_eq!(obj.count.get(), 2);
+    drop(loaded);
+    assert_eq!(obj.count.get(), 1);
+
+    let taken = atomic.take().unwrap();
+    assert!(atomic.load().is_none());
+    assert_eq!(obj.count.get(), 1);
+
+    atomic.store(Some(taken));
+    drop(atomic);
+    assert_eq!(obj.count.get(), 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn once_com_ptr() {
+    let obj = MyClass::new();
+
+    let once = OnceComPtr::<IUnknown>::new();
+    assert!(once.get().is_none());
+    assert!(!once.is_initialized());
+
+    let ptr =
+        once.get_or_init(|| unsafe { ComPtr::from_raw(&obj as *const MyClass as *mut IUnknown) });
+    assert!(ptr.is_some());
+    assert_eq!(obj.count.get(), 1);
+
+    let ptr = once.get_or_init(|| panic!("initialized twice"));
+    assert!(ptr.is_some());
+    assert!(once.set(None).is_err());
+    assert_eq!(obj.count.get(), 1);
+
+    drop(once);
+    assert_eq!(obj.count.get(), 0);
+}
+
+#[test]
+fn interface_map() {
+    let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    let mut map = InterfaceMap::new();
+    assert!(map.is_empty());
+
+    assert!(map
+        .insert(obj.to_com_ptr::<IOtherInterface>().unwrap())
+        .is_none());
+    assert!(map
+        .insert(obj.to_com_ptr::<IDerivedInterface>().unwrap())
+        .is_none());
+    assert_eq!(map.len(), 2);
+    assert!(map.contains(&IOtherInterface::IID));
+    assert!(!map.contains(&IMyInterface::IID));
+
+    assert_eq!(map.get::<IOtherInterface>().unwrap().other_method(), 2);
+    assert_eq!(map.get::<IDerivedInterface>().unwrap().derived_method(), 3);
+    assert!(map.get::<IMyInterface>().is_none());
+
+    let previous = map.insert(obj.to_com_ptr::<IOtherInterface>().unwrap());
+    assert!(previous.is_some());
+    assert_eq!(map.len(), 2);
+
+    assert!(map.remove::<IDerivedInterface>().is_some());
+    assert!(map.get::<IDerivedInterface>().is_none());
+
+    map.clear();
+    assert!(map.is_empty());
+}
+
+#[test]
+fn com_wrapper_downcast() {
+    let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    let ptr = obj.to_com_ptr::<IOtherInterface>().unwrap();
+    let recovered = ComWrapper::<MyClass3>::try_from_com_ptr(&ptr).unwrap();
+    assert_eq!(recovered.z, 3);
+    assert!(ComWrapper::<MyClass2>::try_from_com_ptr(&ptr).is_none());
+
+    let unknown = ptr.upcast::<IUnknown>();
+    let recovered = ComWrapper::<MyClass3>::try_from_com_ref(unknown.as_com_ref()).unwrap();
+    assert_eq!(recovered.x, 1);
+
+    let foreign = MyClass::new();
+    let foreign_ptr =
+        unsafe { ComPtr::from_raw(&foreign as *const MyClass as *mut IUnknown) }.unwrap();
+    assert!(ComWrapper::<MyClass3>::try_from_com_ptr(&foreign_ptr).is_none());
+    assert_eq!(foreign.count.get(), 1);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "no outstanding interface pointer references")]
+fn com_wrapper_release_underflow() {
+    let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    let ptr = obj.to_com_ptr::<IMyInterface>().unwrap();
+    drop(ptr);
+
+    // Simulate a misbehaving host releasing an interface pointer a second time. This is called
+    // directly rather than via the virtual table, since panics cannot unwind out of the
+    // `extern "system"` thunks.
+    unsafe {
+        let data = &*obj as *const MyClass3 as *mut MyClass3;
+        <ComWrapper<MyClass3> as Wrapper<MyClass3>>::release(data);
+    }
+}
+
+struct MyClass4 {
+    events: Rc<RefCell<Vec<&'static str>>>,
+}
+
+impl Class for MyClass4 {
+    type Interfaces = (IMyInterface,);
+
+    fn pre_destroy(&self) {
+        self.events.borrow_mut().push("pre_destroy");
+    }
+}
+
+impl Drop for MyClass4 {
+    fn drop(&mut self) {
+        self.events.borrow_mut().push("drop");
+    }
+}
+
+impl IMyInterfaceTrait for MyClass4 {
+    fn my_method(&self) -> u32 {
+        0
+    }
+}
+
+#[test]
+fn pre_destroy() {
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let obj = ComWrapper::new(MyClass4 {
+        events: events.clone(),
+    });
+
+    let ptr = obj.to_com_ptr::<IMyInterface>().unwrap();
+    drop(obj);
+    assert!(events.borrow().is_empty());
+
+    drop(ptr);
+    assert_eq!(*events.borrow(), ["pre_destroy", "drop"]);
+}
+
+struct ValidClass {
+    x: u32,
+    z: u32,
+}
+
+impl Class for ValidClass {
+    type Interfaces = (IMyInterface, IDerivedInterface);
+}
+
+impl IMyInterfaceTrait for ValidClass {
+    fn my_method(&self) -> u32 {
+        self.x
+    }
+}
+
+impl IDerivedInterfaceTrait for ValidClass {
+    fn derived_method(&self) -> u32 {
+        self.z
+    }
+}
+
+#[test]
+fn com_object_valid() {
+    assert_com_object_valid::<IUnknown, _>(&ComWrapper::new(ValidClass { x: 1, z: 3 }));
+}
+
+#[test]
+fn com_weak() {
+    let dropped = Rc::new(Cell::new(false));
+    let obj = ComWrapper::new(MyClass2 {
+        x: 1,
+        y: 2,
+        dropped: dropped.clone(),
+    });
+
+    let weak = obj.downgrade::<IOtherInterface>().unwrap();
+    assert!(obj.downgrade::<IDerivedInterface>().is_none());
+
+    let ptr = weak.upgrade().unwrap();
+    assert_eq!(ptr.other_method(), 2);
+    assert_eq!(
+        ptr.as_ptr(),
+        obj.as_com_ref::<IOtherInterface>().unwrap().as_ptr()
+    );
+
+    let weak_2 = weak.clone();
+    drop(obj);
+    assert!(!dropped.get());
+    assert!(weak_2.upgrade().is_some());
+
+    drop(ptr);
+    assert!(dropped.get());
+    assert!(weak.upgrade().is_none());
+    assert!(weak_2.upgrade().is_none());
+}
+
+#[com_scrape_macros::com_interface(iid = *b"eeeeeeeeeeeeeeee", bases(IMyInterface, IUnknown), crate = crate)]
+trait IMacroInterface: IMyInterfaceTrait {
+    unsafe fn add(&self, a: u32, b: u32) -> u32;
+    unsafe fn set(&self, value: u32);
+}
+
+struct MyClass5 {
+    value: Cell<u32>,
+}
+
+impl Class for MyClass5 {
+    type Interfaces = (IMacroInterface,);
+}
+
+impl IMyInterfaceTrait for MyClass5 {
+    fn my_method(&self) -> u32 {
+        self.value.get()
+    }
+}
+
+impl IMacroInterfaceTrait for MyClass5 {
+    unsafe fn add(&self, a: u32, b: u32) -> u32 {
+        a + b
+    }
+
+    unsafe fn set(&self, value: u32) {
+        self.value.set(value);
+    }
+}
+
+#[test]
+fn com_interface_macro() {
+    assert!(IMacroInterface::inherits(&IMyInterface::IID));
+    assert!(IMacroInterface::inherits(&IUnknown::IID));
+    assert!(!IMacroInterface::inherits(&IOtherInterface::IID));
+
+    let obj = ComWrapper::new(MyClass5 {
+        value: Cell::new(0),
+    });
+    assert_com_object_valid::<IUnknown, _>(&obj);
+
+    let ptr = obj.to_com_ptr::<IMacroInterface>().unwrap();
+    unsafe {
+        assert_eq!(ptr.add(1, 2), 3);
+        ptr.set(4);
+    }
+    assert_eq!(ptr.my_method(), 4);
+
+    let base = ptr.cast::<IMyInterface>().unwrap();
+    assert_eq!(base.my_method(), 4);
+    let ptr = base.cast::<IMacroInterface>().unwrap();
+    unsafe {
+        assert_eq!(ptr.add(2, 3), 5);
+    }
+}
+
+#[test]
+fn com_wrapper_local() {
+    let dropped = Rc::new(Cell::new(false));
+    let obj = ComWrapperLocal::new(MyClass2 {
+        x: 1,
+        y: 2,
+        dropped: dropped.clone(),
+    });
+
+    let com_ref = unsafe { obj.as_com_ref::<IMyInterface>() }.unwrap();
+    assert_eq!(com_ref.my_method(), 1);
+
+    let com_ptr_1 = unsafe { obj.to_com_ptr::<IOtherInterface>() }.unwrap();
+    assert_eq!(com_ptr_1.other_method(), 2);
+
+    let com_ptr_2 = com_ptr_1.cast::<IMyInterface>().unwrap();
+    assert_eq!(com_ptr_2.my_method(), 1);
+
+    let obj_2 = ComWrapperLocal::<MyClass2>::try_from_com_ptr(&com_ptr_2).unwrap();
+    assert_eq!(obj_2.x, 1);
+    assert!(ComWrapper::<MyClass2>::try_from_com_ptr(&com_ptr_2).is_none());
+
+    drop(obj);
+    drop(obj_2);
+    drop(com_ptr_1);
+    assert!(!dropped.get());
+
+    drop(com_ptr_2);
+    assert!(dropped.get());
+}
+
+#[test]
+#[cfg(all(debug_assertions, feature = "std"))]
+fn com_wrapper_local_wrong_thread() {
+    let obj = ComWrapperLocal::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    // Call add_ref directly rather than via an interface pointer, since a panic inside an
+    // extern "system" function would abort the test process.
+    let data = &*obj as *const MyClass3 as usize;
+    let result = std::thread::spawn(move || unsafe {
+        <ComWrapperLocal<MyClass3> as Wrapper<MyClass3>>::add_ref(data as *mut MyClass3);
+    })
+    .join();
+    assert!(result.is_err());
+}
+
+#[test]
+fn com_wrapper_get_mut_try_unwrap() {
+    let dropped = Rc::new(Cell::new(false));
+    let mut obj = ComWrapper::new(MyClass2 {
+        x: 1,
+        y: 2,
+        dropped: dropped.clone(),
+    });
+
+    ComWrapper::get_mut(&mut obj).unwrap().x = 3;
+    assert_eq!(obj.to_com_ptr::<IMyInterface>().unwrap().my_method(), 3);
+
+    let ptr = obj.to_com_ptr::<IOtherInterface>().unwrap();
+    assert!(ComWrapper::get_mut(&mut obj).is_none());
+    let obj = ComWrapper::try_unwrap(obj).err().unwrap();
+
+    drop(ptr);
+    let weak = obj.downgrade::<IMyInterface>().unwrap();
+    let mut obj = ComWrapper::try_unwrap(obj).ok().unwrap();
+    assert!(weak.upgrade().is_none());
+    assert!(!dropped.get());
+
+    obj.y = 4;
+    let obj = ComWrapperLocal::new(obj);
+    let ptr = unsafe { obj.to_com_ptr::<IOtherInterface>() }.unwrap();
+    assert_eq!(ptr.other_method(), 4);
+    drop(ptr);
+    let data = ComWrapperLocal::try_unwrap(obj).ok().unwrap();
+    assert!(!dropped.get());
+
+    drop(data);
+    assert!(dropped.get());
+}
+
+struct MyClass6 {
+    this: ComWeak<IMyInterface>,
+}
+
+impl Class for MyClass6 {
+    type Interfaces = (IMyInterface,);
+}
+
+impl IMyInterfaceTrait for MyClass6 {
+    fn my_method(&self) -> u32 {
+        let this = self.this.upgrade().unwrap();
+        this.as_ptr() as usize as u32
+    }
+}
+
+#[test]
+fn com_wrapper_new_cyclic() {
+    let obj = ComWrapper::new_cyclic(|this: ComWeak<IMyInterface>| {
+        assert!(this.upgrade().is_none());
+        MyClass6 { this }
+    });
+
+    let ptr = obj.to_com_ptr::<IMyInterface>().unwrap();
+    assert_eq!(ptr.my_method(), ptr.as_ptr() as usize as u32);
+
+    let weak = obj.this.clone();
+    drop(obj);
+    drop(ptr);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn query_interface_fuzz() {
+    const THREADS: usize = 4;
+    const ITERATIONS: usize = 10_000;
+
+    let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+    let ptr = obj.to_com_ptr::<IUnknown>().unwrap();
+    let initial_count = unsafe {
+        IUnknown::add_ref(ptr.as_ptr());
+        IUnknown::release(ptr.as_ptr())
+    };
+
+    let known = [
+        IUnknown::IID,
+        IMyInterface::IID,
+        IOtherInterface::IID,
+        IDerivedInterface::IID,
+    ];
+
+    let threads = (0..THREADS)
+        .map(|seed| {
+            let raw = ptr.as_ptr() as usize;
+            std::thread::spawn(move || {
+                let ptr = raw as *mut IUnknown;
+
+                // xorshift64
+                let mut state = 0x9E37_79B9_7F4A_7C15u64 ^ seed as u64;
+                let mut next = move || {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state
+                };
+
+                for i in 0..ITERATIONS {
+                    let iid = if i % 2 == 0 {
+                        let mut iid = [0; 16];
+                        iid[..8].copy_from_slice(&next().to_le_bytes());
+                        iid[8..].copy_from_slice(&next().to_le_bytes());
+                        iid
+                    } else {
+                        // Flip a single bit of a known IID
+                        let bits = next();
+                        let mut iid = known[(bits % known.len() as u64) as usize];
+                        let bit = (bits >> 8) % 128;
+                        iid[(bit / 8) as usize] ^= 1 << (bit % 8);
+                        iid
+                    };
+
+                    let expected = <MyClass3 as Class>::Interfaces::query(&iid).is_some();
+                    unsafe {
+                        let result = IUnknown::query_interface(ptr, &iid);
+                        assert_eq!(result.is_some(), expected);
+                        if let Some(obj) = result {
+                            IUnknown::release(obj as *mut IUnknown);
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let final_count = unsafe {
+        IUnknown::add_ref(ptr.as_ptr());
+        IUnknown::release(ptr.as_ptr())
+    };
+    assert_eq!(final_count, initial_count);
+}
+
+#[repr(C)]
+struct Outer {
+    unknown: IUnknown,
+    count: Cell<usize>,
+    inner: RefCell<Option<ComWrapper<MyClass2>>>,
+}
+
+impl Outer {
+    const VTBL: IUnknownVtbl = IUnknownVtbl {
+        query_interface: Self::query_interface,
+        add_ref: Self::add_ref,
+        release: Self::release,
+    };
+
+    unsafe extern "system" fn query_interface(
+        this: *mut IUnknown,
+        iid: *const Guid,
+        obj: *mut *mut c_void,
+    ) -> c_long {
+        let outer = &*(this as *const Outer);
+        if *iid == IUnknown::IID {
+            outer.count.set(outer.count.get() + 1);
+            *obj = this as *mut c_void;
+            return 0;
+        }
+
+        if *iid == IMyInterface::IID {
+            let inner = outer.inner.borrow();
+            let ptr = inner
+                .as_ref()
+                .unwrap()
+                .to_com_ptr::<IMyInterface>()
+                .unwrap();
+            *obj = ptr.into_raw() as *mut c_void;
+            return 0;
+        }
+
+        1
+    }
+
+    unsafe extern "system" fn add_ref(this: *mut IUnknown) -> c_ulong {
+        let outer = &*(this as *const Outer);
+        outer.count.set(outer.count.get() + 1);
+        outer.count.get() as c_ulong
+    }
+
+    unsafe extern "system" fn release(this: *mut IUnknown) -> c_ulong {
+        let outer = &*(this as *const Outer);
+        outer.count.set(outer.count.get() - 1);
+        outer.count.get() as c_ulong
+    }
+}
+
+#[test]
+fn com_wrapper_aggregated() {
+    let outer = Box::new(Outer {
+        unknown: IUnknown { vtbl: &Outer::VTBL },
+        count: Cell::new(1),
+        inner: RefCell::new(None),
+    });
+    let outer_ptr = &outer.unknown as *const IUnknown as *mut IUnknown;
+
+    let dropped = Rc::new(Cell::new(false));
+    let inner = unsafe {
+        ComWrapper::new_aggregated(
+            MyClass2 {
+                x: 1,
+                y: 2,
+                dropped: dropped.clone(),
+            },
+            ComRef::from_raw(outer_ptr).unwrap(),
+        )
+    };
+    *outer.inner.borrow_mut() = Some(inner.clone());
+    assert_eq!(outer.count.get(), 1);
+
+    // Interface pointers to the aggregated object forward reference counting to the outer object.
+    let my_interface = inner.to_com_ptr::<IMyInterface>().unwrap();
+    assert_eq!(outer.count.get(), 2);
+    assert_eq!(my_interface.my_method(), 1);
+
+    // Querying the aggregated object for the outer object's interfaces returns the outer object.
+    let unknown = my_interface.cast::<IUnknown>().unwrap();
+    assert_eq!(unknown.as_ptr(), outer_ptr);
+    assert_eq!(outer.count.get(), 3);
+
+    // Interfaces of the aggregated object not exposed by the outer object are not available.
+    assert!(my_interface.cast::<IOtherInterface>().is_none());
+    assert_eq!(outer.count.get(), 3);
+
+    // Querying the outer object for the aggregated object's interfaces returns the inner object.
+    let my_interface2 = unknown.cast::<IMyInterface>().unwrap();
+    assert_eq!(my_interface2.as_ptr(), my_interface.as_ptr());
+    assert_eq!(outer.count.get(), 4);
+
+    let weak = inner.downgrade::<IMyInterface>().unwrap();
+    let upgraded = weak.upgrade().unwrap();
+    assert_eq!(outer.count.get(), 5);
+
+    drop(upgraded);
+    drop(my_interface2);
+    drop(unknown);
+    drop(my_interface);
+    assert_eq!(outer.count.get(), 1);
+    assert!(!dropped.get());
+
+    drop(inner);
+    outer.inner.borrow_mut().take();
+    assert!(dropped.get());
+    assert!(weak.upgrade().is_none());
+}
+
+struct MyClass7 {
+    x: u32,
+}
+
+impl Class for MyClass7 {
+    type Interfaces = (IMyInterface,);
+}
+
+impl IMyInterfaceTrait for MyClass7 {
+    fn my_method(&self) -> u32 {
+        self.x
+    }
+}
+
+static STATIC_OBJECT: ComWrapperStatic<MyClass7> = ComWrapperStatic::new(MyClass7 { x: 7 });
+
+#[test]
+fn com_wrapper_static() {
+    assert_eq!(STATIC_OBJECT.x, 7);
+
+    let my_interface: ComRef<'static, IMyInterface> = STATIC_OBJECT.as_com_ref().unwrap();
+    assert_eq!(my_interface.my_method(), 7);
+    assert!(STATIC_OBJECT.as_com_ref::<IOtherInterface>().is_none());
+
+    let ptr = my_interface.as_ptr();
+    unsafe {
+        assert_eq!(IMyInterface::add_ref(ptr), 1);
+        assert_eq!(IMyInterface::release(ptr), 1);
+        assert_eq!(IMyInterface::release(ptr), 1);
+    }
+
+    let unknown = my_interface.cast::<IUnknown>().unwrap();
+    assert_eq!(unknown.as_ptr() as *mut IMyInterface, ptr);
+    drop(unknown);
+
+    let com_ptr = STATIC_OBJECT.to_com_ptr::<IMyInterface>().unwrap();
+    assert_eq!(com_ptr.my_method(), 7);
+}
+
+#[test]
+fn out_ptr() {
+    unsafe {
+        assert!(OutPtr::<IUnknown>::from_raw(ptr::null_mut()).is_none());
+    }
+
+    let dropped = Rc::new(Cell::new(false));
+    let obj = ComWrapper::new(MyClass2 {
+        x: 1,
+        y: 2,
+        dropped: dropped.clone(),
+    });
+
+    let mut raw: *mut c_void = ptr::dangling_mut();
+    let out = unsafe { OutPtr::<IUnknown>::from_raw_void(&mut raw).unwrap() };
+    out.write(obj.to_com_ptr::<IMyInterface>().unwrap());
+    assert_eq!(
+        raw,
+        obj.as_com_ref::<IMyInterface>().unwrap().as_ptr() as *mut c_void
+    );
+
+    let mut raw2: *mut IUnknown = ptr::dangling_mut();
+    let out = unsafe { OutPtr::from_raw(&mut raw2).unwrap() };
+    out.write_option(None::<ComPtr<IUnknown>>);
+    assert!(raw2.is_null());
+
+    // The reference written to the out-parameter is owned by the caller
+    drop(obj);
+    assert!(!dropped.get());
+    drop(unsafe { ComPtr::from_raw(raw as *mut IUnknown) });
+    assert!(dropped.get());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn catch_panic_handler() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CAUGHT: AtomicUsize = AtomicUsize::new(0);
+
+    set_panic_handler(|payload| {
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"oops"));
+        CAUGHT.fetch_add(1, Ordering::Relaxed);
+    });
+
+    assert_eq!(catch_panic(|| 1, || 2), 1);
+    assert_eq!(CAUGHT.load(Ordering::Relaxed), 0);
+
+    assert_eq!(catch_panic(|| -> i32 { panic!("oops") }, || 2), 2);
+    assert_eq!(CAUGHT.load(Ordering::Relaxed), 1);
+
+    // A panic inside the handler is not propagated
+    set_panic_handler(|_| panic!("handler"));
+    assert_eq!(catch_panic(|| -> i32 { panic!("oops") }, || 3), 3);
+}
+
+#[test]
+fn debug_fmt() {
+    let obj = ComWrapper::new(MyClass7 { x: 0 });
+    let ptr = obj.to_com_ptr::<IMyInterface>().unwrap();
+
+    let debug = format!("{:?}", ptr);
+    assert!(debug.starts_with("ComPtr<com_scrape_types::tests::IMyInterface> { ptr: 0x"));
+    assert!(debug.contains("iid: {62626262-6262-6262-6262-626262626262}"));
+    #[cfg(feature = "debug-ref-count")]
+    assert!(debug.contains("ref_count: 2"));
+
+    let debug = format!("{:?}", ptr.as_com_ref());
+    assert!(debug.starts_with("ComRef<com_scrape_types::tests::IMyInterface> {"));
+}
+
+#[test]
+fn guid_fmt() {
+    let guid = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E,
+        0x0F,
+    ];
+    let expected = if cfg!(target_os = "windows") {
+        "{03020100-0504-0706-0809-0A0B0C0D0E0F}"
+    } else {
+        "{00010203-0405-0607-0809-0A0B0C0D0E0F}"
+    };
+    assert_eq!(format!("{:?}", GuidFmt(&guid)), expected);
+}
+
+#[test]
+fn identity() {
+    use std::collections::HashSet;
+
+    let obj1 = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+    let obj2 = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+
+    let my_interface = obj1.to_com_ptr::<IMyInterface>().unwrap();
+    let other_interface = obj1.to_com_ptr::<IOtherInterface>().unwrap();
+    let other_object = obj2.to_com_ptr::<IMyInterface>().unwrap();
+    assert_ne!(
+        my_interface.as_ptr() as usize,
+        other_interface.as_ptr() as usize
+    );
+
+    assert!(my_interface.identity_eq::<IUnknown, _>(&other_interface));
+    assert!(!my_interface.identity_eq::<IUnknown, _>(&other_object));
+    assert!(my_interface
+        .as_com_ref()
+        .identity_eq::<IUnknown, _>(other_interface.as_com_ref()));
+
+    let mut set = HashSet::new();
+    assert!(set.insert(IdentityKey::<IUnknown>::new(my_interface.as_com_ref()).unwrap()));
+    assert!(!set.insert(IdentityKey::<IUnknown>::new(other_interface.as_com_ref()).unwrap()));
+    assert!(set.insert(IdentityKey::<IUnknown>::new(other_object.as_com_ref()).unwrap()));
+    assert_eq!(set.len(), 2);
+}
+
+#[cfg(feature = "leak-tracking")]
+struct MyClass8;
+
+#[cfg(feature = "leak-tracking")]
+impl Class for MyClass8 {
+    type Interfaces = (IMyInterface,);
+}
+
+#[cfg(feature = "leak-tracking")]
+impl IMyInterfaceTrait for MyClass8 {
+    fn my_method(&self) -> u32 {
+        0
+    }
+}
+
+#[cfg(feature = "leak-tracking")]
+#[test]
+fn leak_tracking() {
+    let count = || {
+        live_objects()
+            .iter()
+            .filter(|object| object.type_name == std::any::type_name::<MyClass8>())
+            .count()
+    };
+
+    let obj1 = ComWrapper::new(MyClass8);
+    let obj2 = ComWrapperLocal::new(MyClass8);
+    assert_eq!(count(), 2);
+
+    let ptr = obj1.to_com_ptr::<IMyInterface>().unwrap();
+    drop(obj1);
+    assert_eq!(count(), 2);
+
+    drop(ptr);
+    assert_eq!(count(), 1);
+
+    let _ = ComWrapperLocal::try_unwrap(obj2);
+    assert_eq!(count(), 0);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_events() {
+    use std::fmt::Debug;
+    use std::string::String;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Default)]
+    struct Fields(Vec<String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    struct Capture(Arc<Mutex<Vec<String>>>);
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0.join(" "));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    tracing::subscriber::with_default(Capture(events.clone()), || {
+        let obj = ComWrapper::new(MyClass3 { x: 1, y: 2, z: 3 });
+        let ptr = obj.to_com_ptr::<IMyInterface>().unwrap();
+        let other = ptr.cast::<IOtherInterface>().unwrap();
+        drop(other);
+        assert!(ptr.cast::<IDerivedInterface>().is_some());
+        assert!(ptr.as_com_ref().cast::<IUnknown>().is_some());
+        let _ = unsafe { IUnknown::query_interface(ptr.as_ptr() as *mut IUnknown, &[0; 16]) };
+    });
+
+    let events = events.lock().unwrap();
+    assert!(events[0].starts_with("message=query_interface object="));
+    assert!(events[0].contains("iid={63636363-6363-6363-6363-636363636363}"));
+    assert!(events[0].contains("interface=\"com_scrape_types::tests::IOtherInterface\""));
+    assert!(events[0].contains("success=true"));
+    assert!(events[1].starts_with("message=release object="));
+    assert!(events[1].contains("count=2"));
+    let failed = events
+        .iter()
+        .rfind(|event| event.starts_with("message=query_interface"))
+        .unwrap();
+    assert!(failed.contains("success=false"));
+    assert!(!failed.contains("interface="));
+}
+
+trait Sample: Copy + Into<f64> + 'static {}
+
+impl Sample for f32 {}
+impl Sample for f64 {}
+
+struct MyGenericClass<T: Sample> {
+    value: T,
+}
+
+impl<T> Class for MyGenericClass<T>
+where
+    T: Sample,
+{
+    type Interfaces = (IMyInterface,);
+}
+
+impl<T> IMyInterfaceTrait for MyGenericClass<T>
+where
+    T: Sample,
+{
+    fn my_method(&self) -> u32 {
+        self.value.into() as u32
+    }
+}
+
+#[test]
+fn generic_class() {
+    let obj_f32 = ComWrapper::new(MyGenericClass { value: 1.0f32 });
+    let obj_f64 = ComWrapper::new(MyGenericClass { value: 2.0f64 });
+
+    let ptr_f32 = obj_f32.to_com_ptr::<IMyInterface>().unwrap();
+    let ptr_f64 = obj_f64.to_com_ptr::<IMyInterface>().unwrap();
+    assert_eq!(ptr_f32.my_method(), 1);
+    assert_eq!(ptr_f64.my_method(), 2);
+
+    let unknown = ptr_f64.cast::<IUnknown>().unwrap();
+    assert!(ComWrapper::<MyGenericClass<f32>>::try_from_com_ptr(&unknown).is_none());
+    assert!(ComWrapper::<MyGenericClass<f64>>::try_from_com_ptr(&unknown).is_some());
+}
+
+struct TestResultCodes;
+
+impl ResultCodes for TestResultCodes {
+    type Code = i32;
+
+    const OK: i32 = 0;
+    const FALSE: i32 = 1;
+    const NO_INTERFACE: i32 = -1;
+    const INVALID_ARGUMENT: i32 = -2;
+    const NOT_IMPLEMENTED: i32 = -3;
+    const INTERNAL_ERROR: i32 = -4;
+    const NOT_INITIALIZED: i32 = -5;
+    const OUT_OF_MEMORY: i32 = -6;
+}
+
+type TestResult = ComResult<TestResultCodes>;
+
+#[test]
+fn com_result() {
+    fn method(fail: bool) -> Result<(), TestResult> {
+        if fail {
+            TestResult::new(-3).into_result()?;
+        }
+        TestResult::OK.into_result()
+    }
+
+    assert_eq!(TestResult::from(method(false)).0, 0);
+    assert_eq!(TestResult::from(method(true)), TestResult::NOT_IMPLEMENTED);
+
+    assert!(TestResult::OK.is_ok());
+    assert_eq!(TestResult::FALSE.into_result(), Err(TestResult::FALSE));
+}
@@ -0,0 +1,67 @@
+use core::ffi::c_void;
+
+#[cfg(feature = "tracing")]
+use super::{ptr::GuidFmt, InterfaceList};
+use super::{Class, Guid};
+
+/// Records a `query_interface` call on an object of class `C`.
+///
+/// Implementations of `query_interface` for Rust classes (such as the ones for root interfaces
+/// like `IUnknown`) should call this after handling the call. With the `tracing` feature enabled,
+/// emits a `tracing` event at the `TRACE` level containing the object's address and class, the
+/// requested IID (along with the interface's name, if it is in `C`'s interface list), and whether
+/// the query succeeded. Otherwise, does nothing.
+#[inline]
+pub fn trace_query_interface<C: Class>(object: *const c_void, iid: &Guid, success: bool) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        target: "com_scrape_types",
+        ?object,
+        class = core::any::type_name::<C>(),
+        iid = ?GuidFmt(iid),
+        interface = C::Interfaces::name(iid),
+        success,
+        "query_interface",
+    );
+
+    #[cfg(not(feature = "tracing"))]
+    let _ = (object, iid, success);
+}
+
+/// Records an `add_ref` call on an object of class `C`, which resulted in a reference count of
+/// `count`.
+///
+/// See [`trace_query_interface`] for details.
+#[inline]
+pub fn trace_add_ref<C: Class>(object: *const c_void, count: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        target: "com_scrape_types",
+        ?object,
+        class = core::any::type_name::<C>(),
+        count,
+        "add_ref",
+    );
+
+    #[cfg(not(feature = "tracing"))]
+    let _ = (object, count);
+}
+
+/// Records a `release` call on an object of class `C`, which resulted in a reference count of
+/// `count`.
+///
+/// See [`trace_query_interface`] for details.
+#[inline]
+pub fn trace_release<C: Class>(object: *const c_void, count: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        target: "com_scrape_types",
+        ?object,
+        class = core::any::type_name::<C>(),
+        count,
+        "release",
+    );
+
+    #[cfg(not(feature = "tracing"))]
+    let _ = (object, count);
+}
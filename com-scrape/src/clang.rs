@@ -127,6 +127,7 @@ impl Drop for TranslationUnit {
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum CursorKind {
+    TranslationUnit,
     Namespace,
     TypedefDecl,
     TypeAliasDecl,
@@ -138,6 +139,7 @@ pub enum CursorKind {
     ClassDecl,
     FieldDecl,
     CxxMethod,
+    Destructor,
     CxxBaseSpecifier,
     Other,
 }
@@ -158,6 +160,7 @@ impl<'a> Cursor<'a> {
     pub fn kind(&self) -> CursorKind {
         #[allow(non_upper_case_globals)]
         match unsafe { clang_getCursorKind(self.cursor) } {
+            CXCursor_TranslationUnit => CursorKind::TranslationUnit,
             CXCursor_Namespace => CursorKind::Namespace,
             CXCursor_TypedefDecl => CursorKind::TypedefDecl,
             CXCursor_TypeAliasDecl => CursorKind::TypeAliasDecl,
@@ -169,6 +172,7 @@ impl<'a> Cursor<'a> {
             CXCursor_ClassDecl => CursorKind::ClassDecl,
             CXCursor_FieldDecl => CursorKind::FieldDecl,
             CXCursor_CXXMethod => CursorKind::CxxMethod,
+            CXCursor_Destructor => CursorKind::Destructor,
             CXCursor_CXXBaseSpecifier => CursorKind::CxxBaseSpecifier,
             _ => CursorKind::Other,
         }
@@ -178,6 +182,18 @@ impl<'a> Cursor<'a> {
         unsafe { StringRef::from_raw(clang_getCursorSpelling(self.cursor)) }
     }
 
+    pub fn semantic_parent(&self) -> Option<Cursor<'a>> {
+        unsafe {
+            let parent = clang_getCursorSemanticParent(self.cursor);
+
+            if clang_Cursor_isNull(parent) != 0 {
+                None
+            } else {
+                Some(Cursor::from_raw(parent))
+            }
+        }
+    }
+
     pub fn is_anonymous(&self) -> bool {
         unsafe { clang_Cursor_isAnonymous(self.cursor) != 0 }
     }
@@ -197,6 +213,10 @@ impl<'a> Cursor<'a> {
         unsafe { clang_equalCursors(self.cursor, clang_getCursorDefinition(self.cursor)) != 0 }
     }
 
+    pub fn has_definition(&self) -> bool {
+        unsafe { clang_Cursor_isNull(clang_getCursorDefinition(self.cursor)) == 0 }
+    }
+
     pub fn type_(&self) -> Option<Type<'a>> {
         let type_ = unsafe { clang_getCursorType(self.cursor) };
         if type_.kind == CXType_Invalid {
@@ -275,6 +295,27 @@ impl<'a> Cursor<'a> {
         unsafe { clang_CXXMethod_isVirtual(self.cursor) != 0 }
     }
 
+    pub fn overridden_cursors(&self) -> Vec<Cursor<'a>> {
+        unsafe {
+            let mut overridden = ptr::null_mut();
+            let mut num_overridden = 0;
+            clang_getOverriddenCursors(self.cursor, &mut overridden, &mut num_overridden);
+
+            if overridden.is_null() {
+                return Vec::new();
+            }
+
+            let cursors = slice::from_raw_parts(overridden, num_overridden as usize)
+                .iter()
+                .map(|cursor| Cursor::from_raw(*cursor))
+                .collect();
+
+            clang_disposeOverriddenCursors(overridden);
+
+            cursors
+        }
+    }
+
     pub fn evaluate(&self) -> EvalResult<'a> {
         unsafe { EvalResult::from_raw(clang_Cursor_Evaluate(self.cursor)) }
     }
@@ -453,6 +494,10 @@ impl<'a> Type<'a> {
         unsafe { clang_Type_getSizeOf(self.type_) as usize }
     }
 
+    pub fn align(&self) -> usize {
+        unsafe { clang_Type_getAlignOf(self.type_) as usize }
+    }
+
     #[allow(unused)]
     pub fn name(&self) -> StringRef<'a> {
         unsafe { StringRef::from_raw(clang_getTypeSpelling(self.type_)) }
@@ -0,0 +1,95 @@
+use crate::{Constant, Record};
+
+/// Information about a struct, union, or interface passed to the hooks of an [`Emitter`].
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub struct RecordInfo<'a> {
+    /// The name of the generated Rust type.
+    pub name: &'a str,
+    /// The path of the Rust module in which the type is generated, relative to the location of the
+    /// bindings.
+    pub module_path: &'a [String],
+    /// Whether the type is a COM interface (i.e. whether it has virtual methods).
+    pub is_interface: bool,
+    /// The parsed definition of the type.
+    pub record: &'a Record,
+}
+
+/// Hooks for customizing the Rust code emitted by
+/// [`Generator::generate`](crate::Generator::generate).
+///
+/// There are three kinds of hooks:
+///
+/// - Naming hooks ([`trait_name`](Emitter::trait_name) and [`vtbl_name`](Emitter::vtbl_name))
+///   determine the names of the items generated for each interface. They are used both for the
+///   definitions of those items and for every reference to them, including references from
+///   derived interfaces.
+/// - Definition hooks ([`record_definition`](Emitter::record_definition) and
+///   [`constant_definition`](Emitter::constant_definition)) can replace the code generated for an
+///   individual item. Returning `None` keeps the default code.
+/// - Insertion hooks ([`record_attributes`](Emitter::record_attributes),
+///   [`record_items`](Emitter::record_items), and [`module_items`](Emitter::module_items)) add
+///   attributes or items to the generated bindings at a fixed point.
+///
+/// Code returned by a hook may span multiple lines, and it is indented to match the surrounding
+/// code. Since it is inserted in the same module as the item it is associated with, it may refer
+/// to that item by its unqualified name.
+///
+/// Every hook has a default implementation which leaves the generated code unchanged, so
+/// implementations only need to override the hooks they use. An `Emitter` is registered with
+/// [`Generator::emitter`](crate::Generator::emitter).
+pub trait Emitter {
+    /// Returns the name of the trait generated for the methods of the interface with the given
+    /// name. Defaults to the name of the interface followed by `Trait`.
+    fn trait_name(&self, interface: &str) -> String {
+        format!("{interface}Trait")
+    }
+
+    /// Returns the name of the virtual table struct generated for the interface with the given
+    /// name. Defaults to the name of the interface followed by `Vtbl`.
+    ///
+    /// Interfaces defined with `com_scrape_types::com_interface` refer to the virtual table of
+    /// their base interface by its default name, so they cannot extend an interface whose virtual
+    /// table has been renamed.
+    fn vtbl_name(&self, interface: &str) -> String {
+        format!("{interface}Vtbl")
+    }
+
+    /// Returns code to use in place of the definition of a struct, union, or interface and all of
+    /// the impls generated for it, or `None` to use the default code.
+    ///
+    /// The replacement is still followed by the items returned by
+    /// [`record_items`](Emitter::record_items), and any types nested inside the record are still
+    /// generated in their own module.
+    fn record_definition(&self, _record: &RecordInfo) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Returns code to use in place of the definition of a constant in the module with the given
+    /// path, or `None` to use the default code.
+    fn constant_definition(
+        &self,
+        _constant: &Constant,
+        _module_path: &[String],
+    ) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Returns attributes to place on the definition of a struct, union, or interface, after the
+    /// `#[repr(C)]` and `#[derive(Copy, Clone)]` attributes (e.g. `#[derive(Debug)]`).
+    fn record_attributes(&self, _record: &RecordInfo) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns items to place after the definition of a struct, union, or interface and all of the
+    /// impls generated for it (e.g. additional trait impls).
+    fn record_items(&self, _record: &RecordInfo) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns items to place at the end of the module with the given path, relative to the
+    /// location of the bindings. The module at the root of the bindings has an empty path.
+    fn module_items(&self, _module_path: &[String]) -> Vec<String> {
+        Vec::new()
+    }
+}
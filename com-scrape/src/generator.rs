@@ -1,10 +1,12 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::error::Error;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use crate::clang::*;
+use crate::emitter::*;
+use crate::index::*;
 use crate::parse::*;
 use crate::print::*;
 
@@ -25,11 +27,15 @@ pub struct Generator {
     pub(crate) include_paths: Vec<PathBuf>,
     pub(crate) skip_types: HashSet<String>,
     pub(crate) skip_interface_traits: HashSet<String>,
+    pub(crate) default_impls: bool,
+    pub(crate) catch_panics: bool,
+    pub(crate) panic_values: HashMap<String, String>,
     pub(crate) constant_parser: Option<Box<dyn Fn(&[String]) -> Option<String>>>,
     pub(crate) iid_generator: Option<Box<dyn Fn(&str) -> String>>,
     pub(crate) query_interface_fn: Option<String>,
     pub(crate) add_ref_fn: Option<String>,
     pub(crate) release_fn: Option<String>,
+    pub(crate) emitter: Option<Box<dyn Emitter>>,
 }
 
 impl Default for Generator {
@@ -38,11 +44,15 @@ impl Default for Generator {
             include_paths: Vec::new(),
             skip_types: HashSet::new(),
             skip_interface_traits: HashSet::new(),
+            default_impls: false,
+            catch_panics: false,
+            panic_values: HashMap::new(),
             constant_parser: None,
             iid_generator: None,
             query_interface_fn: None,
             add_ref_fn: None,
             release_fn: None,
+            emitter: None,
         }
     }
 }
@@ -81,6 +91,40 @@ impl Generator {
         self
     }
 
+    /// Generate `Default` implementations for structs and unions which are not COM interfaces.
+    ///
+    /// The generated implementations return a zero-initialized value, regardless of whether the C++
+    /// type has a default constructor which initializes its fields to other values.
+    pub fn default_impls(mut self, enabled: bool) -> Self {
+        self.default_impls = enabled;
+        self
+    }
+
+    /// Catch panics in the virtual table entries generated for Rust implementations of interfaces.
+    ///
+    /// When enabled, each generated virtual table entry calls the corresponding interface method
+    /// via `com_scrape_types::catch_panic`, so that a panic in the method is reported to the panic
+    /// handler (see `com_scrape_types::set_panic_handler`) instead of unwinding into the caller.
+    /// The entry then returns the value registered for its return type with
+    /// [`panic_value`](Self::panic_value), or a zero-initialized value if there is none.
+    pub fn catch_panics(mut self, enabled: bool) -> Self {
+        self.catch_panics = enabled;
+        self
+    }
+
+    /// Registers a Rust expression to be returned, when a panic is caught (see
+    /// [`catch_panics`](Self::catch_panics)), from methods whose return type is the typedef
+    /// `type_`.
+    ///
+    /// The expression should be in scope where the resulting bindings are placed. This can be used
+    /// to return an error code from methods which return a result type, e.g. `E_UNEXPECTED` for
+    /// `HRESULT`.
+    pub fn panic_value<T: AsRef<str>, U: AsRef<str>>(mut self, type_: T, value: U) -> Self {
+        self.panic_values
+            .insert(type_.as_ref().to_string(), value.as_ref().to_string());
+        self
+    }
+
     /// Registers a callback for parsing constant definitions which `libclang` is not able to
     /// evaluate.
     ///
@@ -134,12 +178,56 @@ impl Generator {
         self
     }
 
+    /// Registers an [`Emitter`] for customizing the generated bindings, replacing any previously
+    /// registered `Emitter`.
+    pub fn emitter<E: Emitter + 'static>(mut self, emitter: E) -> Self {
+        self.emitter = Some(Box::new(emitter));
+        self
+    }
+
     /// Generates Rust bindings for the C++ definitions in `source` and outputs them via `sink`.
     pub fn generate<T: AsRef<str>, W: Write>(
         &self,
         source: T,
         sink: W,
     ) -> Result<(), Box<dyn Error>> {
+        let namespace = self.parse(source)?;
+
+        let mut printer = RustPrinter::new(sink, self);
+        printer.print_namespace(&namespace)?;
+
+        Ok(())
+    }
+
+    /// Generates a Markdown index of the COM interfaces defined in `source` and outputs it via
+    /// `sink`.
+    ///
+    /// For each interface, the index lists the module in which its bindings are located, its
+    /// base interfaces, the interfaces derived from it, and its methods. If `iid` returns a value
+    /// when given the name of an interface, that value will be included in the index as the
+    /// interface's IID.
+    pub fn generate_index<T, W, F>(&self, source: T, sink: W, iid: F) -> Result<(), Box<dyn Error>>
+    where
+        T: AsRef<str>,
+        W: Write,
+        F: Fn(&str) -> Option<String>,
+    {
+        let namespace = self.parse(source)?;
+
+        let mut printer = IndexPrinter::new(sink, &iid);
+        printer.print_index(&namespace)?;
+
+        Ok(())
+    }
+
+    /// Parses the C++ definitions in `source` and returns them without generating any bindings.
+    ///
+    /// The returned [`Namespace`] is the same model from which [`generate`](Self::generate)
+    /// produces bindings, with [`skip_types`](Self::skip_types) and the
+    /// [`constant_parser`](Self::constant_parser) callback already applied. It can be used by
+    /// other tools which consume the parsed model, such as documentation generators or tools for
+    /// comparing versions of an API.
+    pub fn parse<T: AsRef<str>>(&self, source: T) -> Result<Namespace, Box<dyn Error>> {
         if !clang_sys::is_loaded() {
             clang_sys::load()?;
         }
@@ -157,11 +245,6 @@ impl Generator {
             clang_target.as_deref(),
         )?;
 
-        let namespace = Namespace::parse(&unit.cursor(), &self)?;
-
-        let mut printer = RustPrinter::new(sink, &self);
-        printer.print_namespace(&namespace)?;
-
-        Ok(())
+        Namespace::parse(&unit.cursor(), self)
     }
 }
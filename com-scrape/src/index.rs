@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::parse::{Method, Namespace, Record, Type};
+
+struct InterfaceEntry<'a> {
+    module: String,
+    record: &'a Record,
+}
+
+fn collect_interfaces<'a>(
+    namespace: &'a Namespace,
+    module: &str,
+    interfaces: &mut Vec<InterfaceEntry<'a>>,
+) {
+    for record in &namespace.records {
+        if !record.virtual_methods.is_empty() {
+            interfaces.push(InterfaceEntry {
+                module: module.to_string(),
+                record,
+            });
+        }
+
+        let inner_module = join_path(module, &format!("{}_", record.name));
+        collect_interfaces(&record.inner, &inner_module, interfaces);
+    }
+
+    for (name, child) in &namespace.children {
+        collect_interfaces(child, &join_path(module, name), interfaces);
+    }
+}
+
+fn join_path(module: &str, name: &str) -> String {
+    if module.is_empty() {
+        name.to_string()
+    } else {
+        format!("{module}::{name}")
+    }
+}
+
+fn type_name(type_: &Type) -> String {
+    match type_ {
+        Type::Void => "c_void".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Char => "c_char".to_string(),
+        Type::UChar => "c_uchar".to_string(),
+        Type::UShort => "c_ushort".to_string(),
+        Type::UInt => "c_uint".to_string(),
+        Type::ULongLong => "c_ulonglong".to_string(),
+        Type::SChar => "c_schar".to_string(),
+        Type::Short => "c_short".to_string(),
+        Type::Int => "c_int".to_string(),
+        Type::LongLong => "c_longlong".to_string(),
+        Type::Unsigned(size) => format!("u{}", size * 8),
+        Type::Signed(size) => format!("i{}", size * 8),
+        Type::Float => "f32".to_string(),
+        Type::Double => "f64".to_string(),
+        Type::Pointer { is_const, pointee } | Type::Reference { is_const, pointee } => {
+            let mutability = if *is_const { "const" } else { "mut" };
+            format!("*{mutability} {}", type_name(pointee))
+        }
+        Type::Record(name) | Type::Typedef(name) => name.name.clone(),
+        Type::UnnamedRecord(_) => "(unnamed)".to_string(),
+        Type::Array(size, elem) => format!("[{}; {size}]", type_name(elem)),
+    }
+}
+
+fn method_signature(method: &Method) -> String {
+    let mut signature = format!("{}(", method.name);
+
+    for (index, arg) in method.arguments.iter().enumerate() {
+        if index > 0 {
+            signature.push_str(", ");
+        }
+
+        if arg.name.is_empty() {
+            signature.push_str(&format!("_{index}: "));
+        } else {
+            signature.push_str(&format!("{}: ", arg.name));
+        }
+        signature.push_str(&type_name(&arg.type_));
+    }
+    signature.push(')');
+
+    if let Type::Void = method.result_type {
+    } else {
+        signature.push_str(" -> ");
+        signature.push_str(&type_name(&method.result_type));
+    }
+
+    signature
+}
+
+pub struct IndexPrinter<'a, W> {
+    sink: W,
+    iid: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl<'a, W: Write> IndexPrinter<'a, W> {
+    pub fn new(sink: W, iid: &'a dyn Fn(&str) -> Option<String>) -> IndexPrinter<'a, W> {
+        IndexPrinter { sink, iid }
+    }
+
+    pub fn print_index(&mut self, namespace: &Namespace) -> io::Result<()> {
+        let mut interfaces = Vec::new();
+        collect_interfaces(namespace, "", &mut interfaces);
+        interfaces.sort_by(|a, b| (&a.record.name, &a.module).cmp(&(&b.record.name, &b.module)));
+
+        let mut derived: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for interface in &interfaces {
+            if let Some(base) = interface.record.bases.first() {
+                derived
+                    .entry(&base.name.name)
+                    .or_default()
+                    .push(&interface.record.name);
+            }
+        }
+
+        writeln!(self.sink, "# Interface index")?;
+        writeln!(self.sink)?;
+        writeln!(self.sink, "| Interface | Module | IID | Base |")?;
+        writeln!(self.sink, "| --- | --- | --- | --- |")?;
+        for interface in &interfaces {
+            let name = &interface.record.name;
+            let module = &interface.module;
+            let iid = (self.iid)(name)
+                .map(|iid| format!("`{iid}`"))
+                .unwrap_or_default();
+            let base = interface
+                .record
+                .bases
+                .first()
+                .map(|base| format!("`{}`", base.name.name))
+                .unwrap_or_default();
+            writeln!(self.sink, "| `{name}` | `{module}` | {iid} | {base} |")?;
+        }
+
+        for interface in &interfaces {
+            let record = interface.record;
+            let name = &record.name;
+
+            writeln!(self.sink)?;
+            writeln!(self.sink, "## `{name}`")?;
+            writeln!(self.sink)?;
+            writeln!(self.sink, "- Module: `{}`", interface.module)?;
+
+            if let Some(iid) = (self.iid)(name) {
+                writeln!(self.sink, "- IID: `{iid}`")?;
+            }
+
+            if !record.bases.is_empty() {
+                let mut chain = Vec::new();
+                let mut bases = &record.bases;
+                while let Some(base) = bases.first() {
+                    chain.push(format!("`{}`", base.name.name));
+                    bases = &base.bases;
+                }
+                writeln!(self.sink, "- Inherits from: {}", chain.join(" → "))?;
+            }
+
+            if let Some(derived) = derived.get(&**name) {
+                let derived = derived
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>();
+                writeln!(self.sink, "- Derived interfaces: {}", derived.join(", "))?;
+            }
+
+            writeln!(self.sink)?;
+            writeln!(self.sink, "### Methods")?;
+            writeln!(self.sink)?;
+            for method in &record.virtual_methods {
+                writeln!(self.sink, "- `{}`", method_signature(method))?;
+            }
+        }
+
+        Ok(())
+    }
+}
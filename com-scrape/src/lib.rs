@@ -3,8 +3,18 @@
 //! robustness for arbitrary C++ inputs is a non-goal.
 
 mod clang;
+mod emitter;
 mod generator;
+mod index;
 mod parse;
 mod print;
 
+pub use emitter::{Emitter, RecordInfo};
 pub use generator::Generator;
+pub use parse::{
+    Argument, Base, Constant, Field, Method, Namespace, QualifiedName, Record, RecordKind, Type,
+    Typedef, Value,
+};
+
+#[cfg(test)]
+mod tests;
@@ -1,30 +1,43 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
 
 use crate::clang::{self, *};
 use crate::Generator;
 
-#[derive(Clone, Debug)]
+/// The declarations in a C++ namespace (or in the scope of a record or typedef), as returned by
+/// [`Generator::parse`].
+///
+/// Namespaces map to modules in the generated bindings.
+#[derive(Clone, Default, Debug)]
+#[non_exhaustive]
 pub struct Namespace {
+    /// Nested namespaces, keyed by name.
     pub children: BTreeMap<String, Namespace>,
     pub typedefs: Vec<Typedef>,
     pub records: Vec<Record>,
+    /// Records which are declared but never defined.
+    pub opaque_records: Vec<String>,
+    /// Enumerators and constant variables whose values could be evaluated.
     pub constants: Vec<Constant>,
+    /// Rust code produced by the [`constant_parser`](Generator::constant_parser) callback for
+    /// macro definitions which could not be evaluated.
     pub unparsed_constants: Vec<String>,
 }
 
 impl Namespace {
+    /// Creates an empty `Namespace`.
     pub fn new() -> Namespace {
         Namespace {
             children: BTreeMap::new(),
             typedefs: Vec::new(),
             records: Vec::new(),
+            opaque_records: Vec::new(),
             constants: Vec::new(),
             unparsed_constants: Vec::new(),
         }
     }
 
-    pub fn parse(cursor: &Cursor, options: &Generator) -> Result<Namespace, Box<dyn Error>> {
+    pub(crate) fn parse(cursor: &Cursor, options: &Generator) -> Result<Namespace, Box<dyn Error>> {
         let mut parser = Parser::new(options);
         let mut namespace = Namespace::new();
 
@@ -33,71 +46,112 @@ impl Namespace {
         Ok(namespace)
     }
 
+    /// Returns `true` if the namespace and all of its children contain no declarations.
     pub fn is_empty(&self) -> bool {
         self.typedefs.is_empty()
             && self.records.is_empty()
+            && self.opaque_records.is_empty()
             && self.constants.is_empty()
             && self.unparsed_constants.is_empty()
             && self.children.values().all(|child| child.is_empty())
     }
 }
 
+/// A typedef, type alias, or enum declaration. Enums are represented as a typedef for their
+/// underlying integer type.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Typedef {
     pub name: String,
     pub type_: Type,
+    /// Declarations nested in the scope of the typedef (e.g. the enumerators of an enum).
     pub inner: Namespace,
 }
 
+/// Whether a [`Record`] is a struct (or class) or a union.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum RecordKind {
     Struct,
     Union,
 }
 
+/// A struct, class, or union definition. Records with virtual methods are COM interfaces.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Record {
     pub name: String,
     pub kind: RecordKind,
+    /// The size of the record in bytes, as computed by clang for the target platform.
+    pub size: usize,
+    /// The alignment of the record in bytes, as computed by clang for the target platform.
+    pub align: usize,
     pub fields: Vec<Field>,
     pub bases: Vec<Base>,
     pub virtual_methods: Vec<Method>,
+    /// If the record declares a virtual destructor which does not override one in a base class,
+    /// the index in `virtual_methods` before which its virtual table entries are located.
+    pub virtual_destructor: Option<usize>,
+    /// Declarations nested in the scope of the record.
     pub inner: Namespace,
 }
 
+/// A base class of a [`Record`].
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Base {
-    pub name: String,
+    pub name: QualifiedName,
+    /// The base classes of the base class itself.
     pub bases: Vec<Base>,
 }
 
+/// A field of a [`Record`].
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Field {
+    /// The name of the field, or `None` for an anonymous struct or union member.
     pub name: Option<String>,
     pub type_: Type,
 }
 
+/// A virtual method of a [`Record`].
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Method {
+    /// The name of the method in the generated bindings. Overloads of a method after the first
+    /// are given a numeric suffix (e.g. `foo`, `foo1`, `foo2`) so that each one is unique. A
+    /// suffix is skipped if another method in the same record is declared with the resulting name.
     pub name: String,
+    /// The name of the method as declared in C++.
+    pub cxx_name: String,
     pub arguments: Vec<Argument>,
     pub result_type: Type,
 }
 
+/// An argument of a [`Method`].
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Argument {
     pub name: String,
     pub type_: Type,
 }
 
+/// An enumerator or constant variable.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Constant {
     pub name: String,
     pub type_: Type,
     pub value: Value,
 }
 
+/// A C++ type.
+///
+/// Fundamental types with a fixed width on all supported platforms are represented by their own
+/// variants, while types whose width differs between platforms (e.g. `long` and `wchar_t`) are
+/// represented as [`Signed`](Type::Signed) or [`Unsigned`](Type::Unsigned) along with their width
+/// in bytes for the target platform.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum Type {
     Void,
     Bool,
@@ -105,15 +159,12 @@ pub enum Type {
     UChar,
     UShort,
     UInt,
-    ULong,
     ULongLong,
     SChar,
     Short,
     Int,
-    Long,
     LongLong,
     Unsigned(usize),
-    #[allow(unused)]
     Signed(usize),
     Float,
     Double,
@@ -125,12 +176,58 @@ pub enum Type {
         is_const: bool,
         pointee: Box<Type>,
     },
-    Record(String),
-    UnnamedRecord(Record),
-    Typedef(String),
+    Record(QualifiedName),
+    /// An anonymous record, which is given a generated name in the bindings.
+    UnnamedRecord(Box<Record>),
+    Typedef(QualifiedName),
+    /// An array with the given number of elements.
     Array(usize, Box<Type>),
 }
 
+/// The name of a declaration along with the path of the module in which it will be located in the
+/// generated bindings.
+///
+/// Namespaces map to modules of the same name, and types nested inside a record or typedef `Name`
+/// map to a `Name_` module.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[non_exhaustive]
+pub struct QualifiedName {
+    pub scope: Vec<String>,
+    pub name: String,
+}
+
+impl QualifiedName {
+    fn from_decl(decl: &Cursor) -> QualifiedName {
+        let mut scope = Vec::new();
+
+        let mut parent = decl.semantic_parent();
+        while let Some(cursor) = parent {
+            match cursor.kind() {
+                CursorKind::TranslationUnit => break,
+                CursorKind::Namespace if !cursor.is_anonymous() => {
+                    scope.push(cursor.name().to_str().unwrap().to_string());
+                }
+                CursorKind::StructDecl | CursorKind::UnionDecl | CursorKind::ClassDecl
+                    if !cursor.is_anonymous() =>
+                {
+                    scope.push(format!("{}_", cursor.name().to_str().unwrap()));
+                }
+                _ => {}
+            }
+
+            parent = cursor.semantic_parent();
+        }
+
+        scope.reverse();
+
+        QualifiedName {
+            scope,
+            name: decl.name().to_str().unwrap().to_string(),
+        }
+    }
+}
+
+/// The value of a [`Constant`].
 #[derive(Clone, Debug)]
 pub enum Value {
     Signed(i64),
@@ -301,6 +398,13 @@ impl<'a> Parser<'a> {
                         let record = self.parse_record(cursor.type_().unwrap())?;
                         namespace.records.push(record);
                     }
+                } else if !cursor.has_definition() {
+                    // Records which are only ever forward-declared can still be referred to via
+                    // pointers, so emit an opaque type for them
+                    let name = cursor.name().to_str().unwrap().to_string();
+                    if !namespace.opaque_records.contains(&name) {
+                        namespace.opaque_records.push(name);
+                    }
                 }
             }
             _ => {}
@@ -318,8 +422,11 @@ impl<'a> Parser<'a> {
             _ => unreachable!(),
         };
 
+        self.check_vtable_layout(&decl)?;
+
         let mut fields = Vec::new();
         let mut virtual_methods = Vec::new();
+        let mut virtual_destructor = None;
         decl.visit_children(|cursor| -> Result<(), Box<dyn Error>> {
             match cursor.kind() {
                 // Check for UnionDecl to handle anonymous unions
@@ -334,6 +441,13 @@ impl<'a> Parser<'a> {
 
                     fields.push(Field { name, type_ });
                 }
+                // A destructor which overrides a base class destructor reuses the base class's
+                // virtual table entries
+                CursorKind::Destructor
+                    if cursor.is_virtual() && cursor.overridden_cursors().is_empty() =>
+                {
+                    virtual_destructor = Some(virtual_methods.len());
+                }
                 CursorKind::CxxMethod => {
                     if cursor.is_virtual() {
                         let mut arguments = Vec::new();
@@ -366,8 +480,11 @@ impl<'a> Parser<'a> {
                             .parse_type(cursor.result_type().unwrap(), cursor.location())
                             .unwrap();
 
+                        let cxx_name = cursor.name().to_str().unwrap().to_string();
+
                         virtual_methods.push(Method {
-                            name: cursor.name().to_str().unwrap().to_string(),
+                            name: cxx_name.clone(),
+                            cxx_name,
                             arguments,
                             result_type,
                         });
@@ -379,6 +496,26 @@ impl<'a> Parser<'a> {
             Ok(())
         })?;
 
+        // Give overloads after the first a numeric suffix. Suffixes which would collide with the
+        // name of another method (e.g. `foo1` for the second overload of `foo` when `foo1` is also
+        // declared) are skipped.
+        let mut taken = virtual_methods
+            .iter()
+            .map(|method| method.cxx_name.clone())
+            .collect::<HashSet<_>>();
+        let mut seen = HashSet::new();
+        for method in &mut virtual_methods {
+            if seen.insert(method.cxx_name.clone()) {
+                continue;
+            }
+
+            let mut index = 1;
+            while !taken.insert(format!("{}{index}", method.cxx_name)) {
+                index += 1;
+            }
+            method.name = format!("{}{index}", method.cxx_name);
+        }
+
         let bases = self.collect_bases(&decl)?;
 
         let mut inner = Namespace::new();
@@ -387,13 +524,44 @@ impl<'a> Parser<'a> {
         Ok(Record {
             name,
             kind,
+            size: record.size(),
+            align: record.align(),
             fields,
             bases,
             virtual_methods,
+            virtual_destructor,
             inner,
         })
     }
 
+    // The generated virtual tables contain one entry per virtual method following the entries for
+    // the base class, in declaration order for the Itanium ABI and with overloads grouped together
+    // for the MSVC ABI. This matches the actual layouts only when none of the methods override a
+    // base class method (including overrides with covariant return types). libclang does not
+    // expose vtable layouts directly, so check for this case instead and report an error rather
+    // than silently generating a mismatched virtual table.
+    fn check_vtable_layout(&self, decl: &Cursor) -> Result<(), Box<dyn Error>> {
+        let record_name = decl.name();
+        let record_name = record_name.to_str().unwrap();
+
+        decl.visit_children(|cursor| -> Result<(), Box<dyn Error>> {
+            if cursor.kind() == CursorKind::CxxMethod
+                && cursor.is_virtual()
+                && !cursor.overridden_cursors().is_empty()
+            {
+                let location = cursor.location();
+                let name = cursor.name();
+                let name = name.to_str().unwrap();
+                return Err(format!(
+                    "error at {location}: virtual method {record_name}::{name} overrides a base class method"
+                )
+                .into());
+            }
+
+            Ok(())
+        })
+    }
+
     fn collect_bases(&self, decl: &Cursor) -> Result<Vec<Base>, Box<dyn Error>> {
         let mut bases = Vec::new();
 
@@ -401,11 +569,10 @@ impl<'a> Parser<'a> {
             if cursor.kind() == CursorKind::CxxBaseSpecifier {
                 let decl = cursor.type_().unwrap().declaration();
 
-                let name = decl.name();
                 let transitive_bases = self.collect_bases(&decl)?;
 
                 bases.push(Base {
-                    name: name.to_str().unwrap().to_string(),
+                    name: QualifiedName::from_decl(&decl),
                     bases: transitive_bases,
                 });
             }
@@ -429,13 +596,17 @@ impl<'a> Parser<'a> {
             TypeKind::UShort => Ok(Type::UShort),
             TypeKind::UInt => Ok(Type::UInt),
             TypeKind::SChar => Ok(Type::SChar),
-            TypeKind::Char16 => Ok(Type::Short),
+            // UTF-16 code units are unsigned, so `char16_t` is mapped to `u16` (as is `wchar_t` on
+            // Windows, where it is 16 bits wide)
+            TypeKind::Char16 => Ok(Type::Unsigned(2)),
             TypeKind::WChar => Ok(Type::Unsigned(type_.size())),
-            TypeKind::ULong => Ok(Type::ULong),
+            // The width of `long` differs between platforms (32 bits on Windows, 64 bits on 64-bit
+            // Unix), so it is mapped to a fixed-width type based on its size for the target
+            TypeKind::ULong => Ok(Type::Unsigned(type_.size())),
             TypeKind::ULongLong => Ok(Type::ULongLong),
             TypeKind::Short => Ok(Type::Short),
             TypeKind::Int => Ok(Type::Int),
-            TypeKind::Long => Ok(Type::Long),
+            TypeKind::Long => Ok(Type::Signed(type_.size())),
             TypeKind::LongLong => Ok(Type::LongLong),
             TypeKind::Float => Ok(Type::Float),
             TypeKind::Double => Ok(Type::Double),
@@ -456,17 +627,16 @@ impl<'a> Parser<'a> {
             TypeKind::Record => {
                 let decl = type_.declaration();
                 if decl.is_anonymous() {
-                    Ok(Type::UnnamedRecord(self.parse_record(type_)?))
+                    Ok(Type::UnnamedRecord(Box::new(self.parse_record(type_)?)))
                 } else {
-                    let name = decl.name().to_str().unwrap().to_string();
-                    Ok(Type::Record(name))
+                    Ok(Type::Record(QualifiedName::from_decl(&decl)))
                 }
                 // name = format!("__type{}", namespace.unnamed_record_counter);
                 // namespace.unnamed_record_counter += 1;
             }
             TypeKind::Enum => {
                 let decl = type_.declaration();
-                Ok(Type::Typedef(decl.name().to_str().unwrap().to_string()))
+                Ok(Type::Typedef(QualifiedName::from_decl(&decl)))
             }
             TypeKind::Typedef => {
                 // Skip typedef declarations that are found in system headers
@@ -476,8 +646,7 @@ impl<'a> Parser<'a> {
                     return Ok(self.parse_type(underlying_type, location)?);
                 }
 
-                let name = type_.typedef_name().unwrap().to_str().unwrap().to_string();
-                Ok(Type::Typedef(name))
+                Ok(Type::Typedef(QualifiedName::from_decl(&declaration)))
             }
             TypeKind::ConstantArray => {
                 let size = type_.array_size().unwrap();
@@ -1,7 +1,8 @@
 use std::collections::HashSet;
 use std::io::{self, ErrorKind, Write};
 
-use crate::parse::{Method, Namespace, Record, RecordKind, Type, Value};
+use crate::emitter::{Emitter, RecordInfo};
+use crate::parse::{Method, Namespace, QualifiedName, Record, RecordKind, Type, Value};
 use crate::Generator;
 
 struct UnnamedRecordScope {
@@ -19,11 +20,81 @@ impl UnnamedRecordScope {
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Abi {
+    Itanium,
+    Msvc,
+}
+
+impl Abi {
+    fn cfg(self) -> &'static str {
+        match self {
+            Abi::Itanium => "not(target_env = \"msvc\")",
+            Abi::Msvc => "target_env = \"msvc\"",
+        }
+    }
+}
+
+// Virtual destructors are not declared with an explicit calling convention (unlike interface
+// methods, which are declared `__stdcall` on Windows), so they use the default calling convention
+// for member functions: `__thiscall` on 32-bit x86 Windows, and the C calling convention elsewhere.
+const THISCALL_CFG: &str = "all(windows, target_arch = \"x86\")";
+
+fn destructor_conventions() -> [(String, &'static str); 2] {
+    [
+        (format!("not({THISCALL_CFG})"), "C"),
+        (THISCALL_CFG.to_string(), "thiscall"),
+    ]
+}
+
+enum VtblEntry<'a> {
+    Method(&'a Method),
+    Destructor,
+}
+
+// Returns the entries of a record's virtual table in order. Under the MSVC ABI, overloads of a
+// method are placed together at the position of the first overload, in reverse declaration order.
+fn vtable_order(record: &Record, abi: Abi) -> Vec<VtblEntry<'_>> {
+    let methods = &record.virtual_methods;
+    let mut order = Vec::new();
+
+    for (index, method) in methods.iter().enumerate() {
+        if record.virtual_destructor == Some(index) {
+            order.push(VtblEntry::Destructor);
+        }
+
+        match abi {
+            Abi::Itanium => order.push(VtblEntry::Method(method)),
+            Abi::Msvc => {
+                let first = methods
+                    .iter()
+                    .position(|m| m.cxx_name == method.cxx_name)
+                    .unwrap();
+                if first == index {
+                    let overloads = methods.iter().filter(|m| m.cxx_name == method.cxx_name);
+                    order.extend(overloads.rev().map(VtblEntry::Method));
+                }
+            }
+        }
+    }
+
+    if record.virtual_destructor == Some(methods.len()) {
+        order.push(VtblEntry::Destructor);
+    }
+
+    order
+}
+
+struct DefaultEmitter;
+
+impl Emitter for DefaultEmitter {}
+
 pub struct RustPrinter<'a, W> {
     sink: W,
     options: &'a Generator,
     reserved: HashSet<&'static str>,
     indent_level: usize,
+    module_path: Vec<String>,
     unnamed_records: Vec<UnnamedRecordScope>,
 }
 
@@ -34,6 +105,7 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             options: options,
             reserved: HashSet::from(["type"]),
             indent_level: 0,
+            module_path: Vec::new(),
             unnamed_records: Vec::new(),
         }
     }
@@ -42,6 +114,70 @@ impl<'a, W: Write> RustPrinter<'a, W> {
         "    ".repeat(self.indent_level)
     }
 
+    // Writes the code returned by an Emitter hook at the current indentation level.
+    fn print_emitted(&mut self, items: Vec<String>) -> io::Result<()> {
+        let indent = self.indent();
+        for item in &items {
+            for line in item.lines() {
+                writeln!(self.sink, "{indent}{line}")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record_info<'r>(&'r self, record: &'r Record) -> RecordInfo<'r> {
+        RecordInfo {
+            name: &record.name,
+            module_path: &self.module_path,
+            is_interface: !record.virtual_methods.is_empty(),
+            record,
+        }
+    }
+
+    // Returns the registered Emitter, or an Emitter which leaves the generated code unchanged.
+    fn emitter(&self) -> &'a dyn Emitter {
+        self.options.emitter.as_deref().unwrap_or(&DefaultEmitter)
+    }
+
+    fn enter_module(&mut self, name: &str) {
+        self.indent_level += 1;
+        self.module_path.push(name.to_string());
+    }
+
+    fn exit_module(&mut self) {
+        self.indent_level -= 1;
+        self.module_path.pop();
+    }
+
+    // Returns a path which refers to the given name from the module currently being printed.
+    fn qualify(&self, name: &QualifiedName) -> String {
+        self.qualify_item(name, &name.name)
+    }
+
+    // Returns a path which refers to the item with the given name in the scope of `name` (e.g. the
+    // trait generated for an interface).
+    fn qualify_item(&self, name: &QualifiedName, item: &str) -> String {
+        let common = self
+            .module_path
+            .iter()
+            .zip(&name.scope)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut path = String::new();
+        for _ in common..self.module_path.len() {
+            path.push_str("super::");
+        }
+        for segment in &name.scope[common..] {
+            path.push_str(segment);
+            path.push_str("::");
+        }
+        path.push_str(item);
+
+        path
+    }
+
     #[rustfmt::skip]
     pub fn print_namespace(&mut self, namespace: &Namespace) -> io::Result<()> {
         self.push_unnamed_records("");
@@ -60,9 +196,9 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                 writeln!(self.sink, "{indent}    #[allow(unused_imports)]")?;
                 writeln!(self.sink, "{indent}    use super::*;")?;
 
-                self.indent_level += 1;
+                self.enter_module(&format!("{name}_"));
                 self.print_namespace(&typedef.inner)?;
-                self.indent_level -= 1;
+                self.exit_module();
 
                 writeln!(self.sink, "{indent}}}")?;
             }
@@ -72,7 +208,27 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             self.print_record(&record)?;
         }
 
+        for name in &namespace.opaque_records {
+            writeln!(self.sink, "{indent}#[repr(C)]")?;
+            writeln!(self.sink, "{indent}pub struct {name} {{")?;
+            writeln!(self.sink, "{indent}    _unused: [u8; 0],")?;
+            // Opt out of the Send, Sync, and Unpin auto traits, which Rust can't assume hold for
+            // an opaque C++ type
+            writeln!(
+                self.sink,
+                "{indent}    _marker: ::std::marker::PhantomData<(*mut u8, ::std::marker::PhantomPinned)>,"
+            )?;
+            writeln!(self.sink, "{indent}}}")?;
+        }
+
         for constant in &namespace.constants {
+            if let Some(emitter) = &self.options.emitter {
+                if let Some(items) = emitter.constant_definition(constant, &self.module_path) {
+                    self.print_emitted(items)?;
+                    continue;
+                }
+            }
+
             let name = &constant.name;
             write!(self.sink, "{indent}pub const {name}: ")?;
             self.print_type(&constant.type_)?;
@@ -95,14 +251,19 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                 writeln!(self.sink, "{indent}    #[allow(unused_imports)]")?;
                 writeln!(self.sink, "{indent}    use super::*;")?;
 
-                self.indent_level += 1;
+                self.enter_module(name);
                 self.print_namespace(child)?;
-                self.indent_level -= 1;
+                self.exit_module();
 
                 writeln!(self.sink, "{indent}}}")?;
             }
         }
 
+        if let Some(emitter) = &self.options.emitter {
+            let items = emitter.module_items(&self.module_path);
+            self.print_emitted(items)?;
+        }
+
         self.pop_unnamed_records()?;
 
         Ok(())
@@ -125,10 +286,10 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             writeln!(self.sink, "{indent}    #[allow(unused_imports)]")?;
             writeln!(self.sink, "{indent}    use super::{name}_::*;")?;
 
-            self.indent_level += 1;
-            self.print_record_body(record)?;
-            self.print_interface(record)?;
-            self.indent_level -= 1;
+            self.enter_module(&format!("__{name}_wrapper"));
+            self.print_record_definition(record)?;
+            self.print_record_items(record)?;
+            self.exit_module();
 
             writeln!(self.sink, "{indent}}}")?;
             writeln!(self.sink, "{indent}pub use __{name}_wrapper::*;")?;
@@ -137,14 +298,14 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             writeln!(self.sink, "{indent}    #[allow(unused_imports)]")?;
             writeln!(self.sink, "{indent}    use super::*;")?;
 
-            self.indent_level += 1;
+            self.enter_module(&format!("{name}_"));
             self.print_namespace(&record.inner)?;
-            self.indent_level -= 1;
+            self.exit_module();
 
             writeln!(self.sink, "{indent}}}")?;
         } else {
-            self.print_record_body(record)?;
-            self.print_interface(record)?;
+            self.print_record_definition(record)?;
+            self.print_record_items(record)?;
         }
 
         self.pop_unnamed_records()?;
@@ -152,6 +313,19 @@ impl<'a, W: Write> RustPrinter<'a, W> {
         Ok(())
     }
 
+    fn print_record_definition(&mut self, record: &Record) -> io::Result<()> {
+        if let Some(emitter) = &self.options.emitter {
+            if let Some(items) = emitter.record_definition(&self.record_info(record)) {
+                return self.print_emitted(items);
+            }
+        }
+
+        self.print_record_body(record)?;
+        self.print_interface(record)?;
+
+        Ok(())
+    }
+
     #[rustfmt::skip]
     fn print_record_body(&mut self, record: &Record) -> io::Result<()> {
         let indent = self.indent();
@@ -163,10 +337,15 @@ impl<'a, W: Write> RustPrinter<'a, W> {
 
         writeln!(self.sink, "{indent}#[repr(C)]")?;
         writeln!(self.sink, "{indent}#[derive(Copy, Clone)]")?;
+        if let Some(emitter) = &self.options.emitter {
+            let attributes = emitter.record_attributes(&self.record_info(record));
+            self.print_emitted(attributes)?;
+        }
         writeln!(self.sink, "{indent}pub {record_kind} {name} {{")?;
 
         if !record.virtual_methods.is_empty() {
-            writeln!(self.sink, "{indent}    pub vtbl: *const {name}Vtbl,")?;
+            let vtbl_name = self.emitter().vtbl_name(name);
+            writeln!(self.sink, "{indent}    pub vtbl: *const {vtbl_name},")?;
         }
 
         let mut anon_counter = 0;
@@ -189,6 +368,33 @@ impl<'a, W: Write> RustPrinter<'a, W> {
         writeln!(self.sink, "{indent}unsafe impl Send for {name} {{}}")?;
         writeln!(self.sink, "{indent}unsafe impl Sync for {name} {{}}")?;
 
+        if self.options.default_impls && record.virtual_methods.is_empty() {
+            writeln!(self.sink, "{indent}impl Default for {name} {{")?;
+            writeln!(self.sink, "{indent}    /// Returns a zero-initialized value, which may differ from the value produced by the C++")?;
+            writeln!(self.sink, "{indent}    /// default constructor.")?;
+            writeln!(self.sink, "{indent}    #[inline]")?;
+            writeln!(self.sink, "{indent}    fn default() -> Self {{")?;
+            writeln!(self.sink, "{indent}        unsafe {{ ::std::mem::zeroed() }}")?;
+            writeln!(self.sink, "{indent}    }}")?;
+            writeln!(self.sink, "{indent}}}")?;
+        }
+
+        // Guard against differences between the layout computed by clang and the layout of the
+        // generated type (e.g. due to the width of `long` differing between platforms)
+        let size = record.size;
+        let align = record.align;
+        writeln!(self.sink, "{indent}const _: () = assert!(::std::mem::size_of::<{name}>() == {size});")?;
+        writeln!(self.sink, "{indent}const _: () = assert!(::std::mem::align_of::<{name}>() == {align});")?;
+
+        Ok(())
+    }
+
+    fn print_record_items(&mut self, record: &Record) -> io::Result<()> {
+        if let Some(emitter) = &self.options.emitter {
+            let items = emitter.record_items(&self.record_info(record));
+            self.print_emitted(items)?;
+        }
+
         Ok(())
     }
 
@@ -197,6 +403,8 @@ impl<'a, W: Write> RustPrinter<'a, W> {
         if !record.virtual_methods.is_empty() {
             let indent = self.indent();
             let name = &record.name;
+            let trait_name = self.emitter().trait_name(name);
+            let vtbl_name = self.emitter().vtbl_name(name);
 
             if record.bases.len() > 1 {
                 return Err(io::Error::new(
@@ -208,7 +416,7 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             {
                 let mut bases = &record.bases;
                 while let Some(base) = bases.first() {
-                    let base_name = &base.name;
+                    let base_name = self.qualify(&base.name);
                     writeln!(self.sink, "{indent}unsafe impl ::com_scrape_types::Inherits<{base_name}> for {name} {{}}")?;
                     bases = &base.bases;
                 }
@@ -244,60 +452,39 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             writeln!(self.sink, "{indent}}}")?;
 
             writeln!(self.sink, "{indent}unsafe impl ::com_scrape_types::Interface for {name} {{")?;
-            writeln!(self.sink, "{indent}    type Vtbl = {name}Vtbl;")?;
+            writeln!(self.sink, "{indent}    type Vtbl = {vtbl_name};")?;
             writeln!(self.sink, "{indent}    const IID: ::com_scrape_types::Guid = {iid_string};")?;
             writeln!(self.sink, "{indent}    #[inline]")?;
             writeln!(self.sink, "{indent}    fn inherits(iid: &::com_scrape_types::Guid) -> bool {{")?;
             write!(self.sink, "{indent}        iid == &Self::IID")?;
             if let Some(base) = record.bases.first() {
-                let base_name = &base.name;
+                let base_name = self.qualify(&base.name);
                 write!(self.sink, " || {base_name}::inherits(iid)")?;
             }
             writeln!(self.sink, "")?;
             writeln!(self.sink, "{indent}    }}")?;
             writeln!(self.sink, "{indent}}}")?;
 
-            writeln!(self.sink, "{indent}#[repr(C)]")?;
-            writeln!(self.sink, "{indent}#[derive(Copy, Clone)]")?;
-            writeln!(self.sink, "{indent}pub struct {name}Vtbl {{")?;
-
-            if let Some(base) = record.bases.first() {
-                let base_name = &base.name;
-                writeln!(self.sink, "{indent}    pub base: {base_name}Vtbl,")?;
-            }
-
-            for method in &record.virtual_methods {
-                let method_name = &method.name;
-                writeln!(
-                    self.sink,
-                    "{indent}    pub {method_name}: unsafe extern \"system\" fn("
-                )?;
-
-                writeln!(self.sink, "{indent}        this: *mut {name},")?;
-
-                self.indent_level += 2;
-                self.print_args(method)?;
-                self.indent_level -= 2;
-
-                write!(self.sink, "{indent}    )")?;
-                if let Type::Void = method.result_type {
-                } else {
-                    write!(self.sink, " -> ")?;
-                    self.print_type(&method.result_type)?;
-                }
-                writeln!(self.sink, ",")?;
+            // Overloaded methods and virtual destructors are laid out differently under the MSVC
+            // ABI, so records containing them need a separate virtual table layout for each ABI.
+            let has_overloads = record.virtual_methods.iter().any(|m| m.name != m.cxx_name);
+            if has_overloads || record.virtual_destructor.is_some() {
+                self.print_vtbl(record, Abi::Itanium, true)?;
+                self.print_vtbl(record, Abi::Msvc, true)?;
+            } else {
+                self.print_vtbl(record, Abi::Itanium, false)?;
             }
 
-            writeln!(self.sink, "{indent}}}")?;
 
             if !self.options.skip_interface_traits.contains(&record.name) {
-                write!(self.sink, "{indent}pub trait {name}Trait")?;
+                write!(self.sink, "{indent}pub trait {trait_name}")?;
                 {
                     let mut bases = &record.bases;
                     while let Some(base) = bases.first() {
-                        if !self.options.skip_interface_traits.contains(&base.name) {
-                            let base_name = &base.name;
-                            write!(self.sink, ": {base_name}Trait")?;
+                        if !self.options.skip_interface_traits.contains(&base.name.name) {
+                            let base_trait = self.emitter().trait_name(&base.name.name);
+                            let base_trait = self.qualify_item(&base.name, &base_trait);
+                            write!(self.sink, ": {base_trait}")?;
                             break;
                         }
                         bases = &base.bases;
@@ -326,15 +513,15 @@ impl<'a, W: Write> RustPrinter<'a, W> {
 
                 writeln!(self.sink, "{indent}}}")?;
 
-                writeln!(self.sink, "{indent}impl<P> {name}Trait for P")?;
+                writeln!(self.sink, "{indent}impl<P> {trait_name} for P")?;
                 writeln!(self.sink, "{indent}where")?;
                 writeln!(self.sink, "{indent}    P: ::com_scrape_types::SmartPtr,")?;
                 writeln!(self.sink, "{indent}    P::Target: ::com_scrape_types::Inherits<{name}>,")?;
                 {
                     let mut bases = &record.bases;
                     while let Some(base) = bases.first() {
-                        if !self.options.skip_interface_traits.contains(&base.name) {
-                            let base_name = &base.name;
+                        if !self.options.skip_interface_traits.contains(&base.name.name) {
+                            let base_name = self.qualify(&base.name);
                             writeln!(self.sink, "{indent}    P::Target: ::com_scrape_types::Inherits<{base_name}>,")?;
                         }
                         bases = &base.bases;
@@ -375,9 +562,9 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                 writeln!(self.sink, "{indent}}}")?;
 
                 writeln!(self.sink, "{indent}impl {name} {{")?;
-                writeln!(self.sink, "{indent}    const fn make_vtbl<C, W, const OFFSET: isize>() -> {name}Vtbl")?;
+                writeln!(self.sink, "{indent}    pub const fn make_vtbl<C, W, const OFFSET: isize>() -> {vtbl_name}")?;
                 writeln!(self.sink, "{indent}    where")?;
-                writeln!(self.sink, "{indent}        C: {name}Trait + ::com_scrape_types::Class,")?;
+                writeln!(self.sink, "{indent}        C: {trait_name} + ::com_scrape_types::Class,")?;
                 writeln!(self.sink, "{indent}        W: ::com_scrape_types::Wrapper<C>,")?;
                 writeln!(self.sink, "{indent}    {{")?;
 
@@ -400,24 +587,64 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                     }
                     writeln!(self.sink, "")?;
                     writeln!(self.sink, "{indent}        where")?;
-                    writeln!(self.sink, "{indent}            C: {name}Trait + ::com_scrape_types::Class,")?;
+                    writeln!(self.sink, "{indent}            C: {trait_name} + ::com_scrape_types::Class,")?;
                     writeln!(self.sink, "{indent}            W: ::com_scrape_types::Wrapper<C>,")?;
                     writeln!(self.sink, "{indent}        {{")?;
-                    writeln!(self.sink, "{indent}            let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;")?;
-                    writeln!(self.sink, "{indent}            let ptr = <W as ::com_scrape_types::Wrapper<C>>::data_from_header(header_ptr);")?;
-                    writeln!(self.sink, "{indent}            (*ptr).{method_name}(")?;
+
+                    // When catching panics, the method call is wrapped in a closure, and the body
+                    // is indented one extra level.
+                    let body_indent = if self.options.catch_panics {
+                        writeln!(self.sink, "{indent}            ::com_scrape_types::catch_panic(")?;
+                        writeln!(self.sink, "{indent}                || {{")?;
+                        self.indent_level += 2;
+                        self.indent()
+                    } else {
+                        indent.clone()
+                    };
+
+                    writeln!(self.sink, "{body_indent}            let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;")?;
+                    writeln!(self.sink, "{body_indent}            let ptr = <W as ::com_scrape_types::Wrapper<C>>::data_from_header(header_ptr);")?;
+                    writeln!(self.sink, "{body_indent}            (*ptr).{method_name}(")?;
 
                     self.indent_level += 4;
                     self.print_arg_names(method)?;
                     self.indent_level -= 4;
 
-                    writeln!(self.sink, "{indent}            )")?;
+                    writeln!(self.sink, "{body_indent}            )")?;
+
+                    if self.options.catch_panics {
+                        self.indent_level -= 2;
+
+                        writeln!(self.sink, "{indent}                }},")?;
+                        write!(self.sink, "{indent}                || ")?;
+                        self.print_panic_value(&method.result_type)?;
+                        writeln!(self.sink, ",")?;
+                        writeln!(self.sink, "{indent}            )")?;
+                    }
+
                     writeln!(self.sink, "{indent}        }}")?;
                 };
 
-                writeln!(self.sink, "{indent}        {name}Vtbl {{")?;
+                // Objects implemented in Rust are destroyed when their reference count reaches
+                // zero, so the destructor entries do nothing.
+                if record.virtual_destructor.is_some() {
+                    let itanium = Abi::Itanium.cfg();
+                    let msvc = Abi::Msvc.cfg();
+                    for (cfg, convention) in destructor_conventions() {
+                        writeln!(self.sink, "{indent}        #[cfg(all({itanium}, {cfg}))]")?;
+                        writeln!(self.sink, "{indent}        unsafe extern \"{convention}\" fn __destructor(_this: *mut {name}) {{}}")?;
+                    }
+                    for (cfg, convention) in destructor_conventions() {
+                        writeln!(self.sink, "{indent}        #[cfg(all({msvc}, {cfg}))]")?;
+                        writeln!(self.sink, "{indent}        unsafe extern \"{convention}\" fn __destructor(this: *mut {name}, _flags: ::std::ffi::c_uint) -> *mut ::std::ffi::c_void {{")?;
+                        writeln!(self.sink, "{indent}            this as *mut ::std::ffi::c_void")?;
+                        writeln!(self.sink, "{indent}        }}")?;
+                    }
+                }
+
+                writeln!(self.sink, "{indent}        {vtbl_name} {{")?;
                 if let Some(base) = record.bases.first() {
-                    let base_name = &base.name;
+                    let base_name = self.qualify(&base.name);
                     writeln!(self.sink, "{indent}            base: {base_name}::make_vtbl::<C, W, OFFSET>(),")?;
                 }
 
@@ -429,6 +656,12 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                     )?;
                 }
 
+                if record.virtual_destructor.is_some() {
+                    writeln!(self.sink, "{indent}            __destructor,")?;
+                    writeln!(self.sink, "{indent}            #[cfg({})]", Abi::Itanium.cfg())?;
+                    writeln!(self.sink, "{indent}            __deleting_destructor: __destructor,")?;
+                }
+
                 writeln!(self.sink, "{indent}        }}")?;
 
                 writeln!(self.sink, "{indent}    }}")?;
@@ -438,7 +671,7 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                 writeln!(self.sink, "{indent}where")?;
                 writeln!(
                     self.sink,
-                    "{indent}    C: {name}Trait + ::com_scrape_types::Class,"
+                    "{indent}    C: {trait_name} + ::com_scrape_types::Class,"
                 )?;
                 writeln!(self.sink, "{indent}    W: ::com_scrape_types::Wrapper<C>,")?;
                 writeln!(self.sink, "{indent}{{")?;
@@ -455,6 +688,76 @@ impl<'a, W: Write> RustPrinter<'a, W> {
         Ok(())
     }
 
+    #[rustfmt::skip]
+    fn print_vtbl(&mut self, record: &Record, abi: Abi, conditional: bool) -> io::Result<()> {
+        let indent = self.indent();
+        let name = &record.name;
+        let vtbl_name = self.emitter().vtbl_name(name);
+
+        if conditional {
+            writeln!(self.sink, "{indent}#[cfg({})]", abi.cfg())?;
+        }
+        writeln!(self.sink, "{indent}#[repr(C)]")?;
+        writeln!(self.sink, "{indent}#[derive(Copy, Clone)]")?;
+        writeln!(self.sink, "{indent}pub struct {vtbl_name} {{")?;
+
+        if let Some(base) = record.bases.first() {
+            let base_vtbl = self.emitter().vtbl_name(&base.name.name);
+            let base_vtbl = self.qualify_item(&base.name, &base_vtbl);
+            writeln!(self.sink, "{indent}    pub base: {base_vtbl},")?;
+        }
+
+        for entry in vtable_order(record, abi) {
+            let method = match entry {
+                VtblEntry::Method(method) => method,
+                VtblEntry::Destructor => {
+                    // The Itanium ABI uses two entries for a virtual destructor (the complete
+                    // object destructor followed by the deleting destructor), while MSVC uses a
+                    // single scalar deleting destructor which takes a flags argument.
+                    for (cfg, convention) in destructor_conventions() {
+                        match abi {
+                            Abi::Itanium => {
+                                writeln!(self.sink, "{indent}    #[cfg({cfg})]")?;
+                                writeln!(self.sink, "{indent}    pub __destructor: unsafe extern \"{convention}\" fn(this: *mut {name}),")?;
+                                writeln!(self.sink, "{indent}    #[cfg({cfg})]")?;
+                                writeln!(self.sink, "{indent}    pub __deleting_destructor: unsafe extern \"{convention}\" fn(this: *mut {name}),")?;
+                            }
+                            Abi::Msvc => {
+                                writeln!(self.sink, "{indent}    #[cfg({cfg})]")?;
+                                writeln!(self.sink, "{indent}    pub __destructor: unsafe extern \"{convention}\" fn(this: *mut {name}, flags: ::std::ffi::c_uint) -> *mut ::std::ffi::c_void,")?;
+                            }
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            let method_name = &method.name;
+            writeln!(
+                self.sink,
+                "{indent}    pub {method_name}: unsafe extern \"system\" fn("
+            )?;
+
+            writeln!(self.sink, "{indent}        this: *mut {name},")?;
+
+            self.indent_level += 2;
+            self.print_args(method)?;
+            self.indent_level -= 2;
+
+            write!(self.sink, "{indent}    )")?;
+            if let Type::Void = method.result_type {
+            } else {
+                write!(self.sink, " -> ")?;
+                self.print_type(&method.result_type)?;
+            }
+            writeln!(self.sink, ",")?;
+        }
+
+        writeln!(self.sink, "{indent}}}")?;
+
+        Ok(())
+    }
+
     fn print_args(&mut self, method: &Method) -> io::Result<()> {
         let mut unnamed_counter = 0;
 
@@ -497,6 +800,16 @@ impl<'a, W: Write> RustPrinter<'a, W> {
         Ok(())
     }
 
+    fn print_panic_value(&mut self, type_: &Type) -> io::Result<()> {
+        match type_ {
+            Type::Void => write!(self.sink, "()"),
+            Type::Typedef(name) if self.options.panic_values.contains_key(&name.name) => {
+                write!(self.sink, "{}", self.options.panic_values[&name.name])
+            }
+            _ => write!(self.sink, "::std::mem::zeroed()"),
+        }
+    }
+
     fn print_type(&mut self, type_: &Type) -> io::Result<()> {
         match type_ {
             Type::Void => write!(self.sink, "::std::ffi::c_void")?,
@@ -505,12 +818,10 @@ impl<'a, W: Write> RustPrinter<'a, W> {
             Type::UChar => write!(self.sink, "::std::ffi::c_uchar")?,
             Type::UShort => write!(self.sink, "::std::ffi::c_ushort")?,
             Type::UInt => write!(self.sink, "::std::ffi::c_uint")?,
-            Type::ULong => write!(self.sink, "::std::ffi::c_ulong")?,
             Type::ULongLong => write!(self.sink, "::std::ffi::c_ulonglong")?,
             Type::SChar => write!(self.sink, "::std::ffi::c_schar")?,
             Type::Short => write!(self.sink, "::std::ffi::c_short")?,
             Type::Int => write!(self.sink, "::std::ffi::c_int")?,
-            Type::Long => write!(self.sink, "::std::ffi::c_long")?,
             Type::LongLong => write!(self.sink, "::std::ffi::c_longlong")?,
             Type::Unsigned(size) => match size {
                 1 => write!(self.sink, "u8")?,
@@ -546,17 +857,17 @@ impl<'a, W: Write> RustPrinter<'a, W> {
                 }
                 self.print_type(pointee)?;
             }
-            Type::Record(name) => write!(self.sink, "{name}")?,
+            Type::Record(name) => write!(self.sink, "{}", self.qualify(name))?,
             Type::UnnamedRecord(record) => {
                 let scope = self.unnamed_record_scope_mut();
                 let name = scope.next_name();
-                let mut record = record.clone();
+                let mut record = (**record).clone();
                 record.name = name.clone();
                 scope.add_record(record);
 
                 write!(self.sink, "{name}")?;
             }
-            Type::Typedef(name) => write!(self.sink, "{name}")?,
+            Type::Typedef(name) => write!(self.sink, "{}", self.qualify(name))?,
             Type::Array(size, elem) => {
                 write!(self.sink, "[")?;
                 self.print_type(elem)?;
@@ -0,0 +1,461 @@
+use std::env;
+use std::ffi::c_long;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+// The snapshots below avoid types whose size differs between the platforms tested in CI (e.g.
+// `long` and `wchar_t`), so that the generated bindings are identical on all of them.
+
+const RECORDS: &str = r#"
+typedef int int32;
+typedef char16_t String128[128];
+
+struct Opaque;
+
+struct Point {
+    float x;
+    float y;
+};
+
+union Value {
+    int32 i;
+    double d;
+    Point p;
+};
+
+struct Buffer {
+    unsigned char bytes[16];
+    float matrix[2][3];
+    Point points[4];
+    String128 name;
+    Opaque* handle;
+    const Point* origin;
+};
+
+struct Tagged {
+    int kind;
+    union {
+        int i;
+        float f;
+    };
+};
+"#;
+
+const ENUMS: &str = r#"
+enum Kind : int {
+    kNone = 0,
+    kNegative = -1,
+    kLarge = 100,
+};
+
+enum class Mode : unsigned char {
+    Off,
+    On,
+};
+
+enum : unsigned int {
+    kAnonymous = 7,
+};
+
+const int kMaxCount = 16;
+const unsigned int kFlags = 0x10u;
+const double kScale = 0.5;
+
+struct Settings {
+    Kind kind;
+    Mode mode;
+};
+"#;
+
+const NAMESPACES: &str = r#"
+namespace outer {
+namespace inner {
+
+typedef unsigned short Id;
+
+struct Item {
+    Id id;
+};
+
+} // namespace inner
+
+struct Container {
+    struct Entry {
+        inner::Item item;
+        int count;
+    };
+
+    Entry entries[2];
+    inner::Item* first;
+};
+
+} // namespace outer
+
+namespace other {
+
+struct Holder {
+    outer::Container container;
+    outer::Container::Entry entry;
+    outer::inner::Id id;
+};
+
+} // namespace other
+"#;
+
+const INTERFACES: &str = r#"
+typedef int result;
+typedef char16_t String128[128];
+
+namespace base {
+
+class IUnknown {
+public:
+    virtual result queryInterface(const char* iid, void** obj) = 0;
+    virtual unsigned int addRef() = 0;
+    virtual unsigned int release() = 0;
+};
+
+} // namespace base
+
+namespace api {
+namespace v1 {
+
+class IWidget : public base::IUnknown {
+public:
+    virtual void setValue(int value) = 0;
+    virtual void setValue(double value) = 0;
+    virtual double getValue() = 0;
+    virtual result getName(String128 name) = 0;
+};
+
+class IDisposable : public IWidget {
+public:
+    virtual ~IDisposable() {}
+    virtual bool isDisposed() const = 0;
+};
+
+class IResettable : public IDisposable {
+public:
+    virtual ~IResettable() {}
+    virtual void reset() = 0;
+};
+
+} // namespace v1
+} // namespace api
+"#;
+
+const CALLBACKS: &str = r#"
+typedef int result;
+
+class ICallback {
+public:
+    virtual result invoke(int value) = 0;
+    virtual void cancel() = 0;
+    virtual float progress() = 0;
+};
+"#;
+
+const EMITTER: &str = r#"
+namespace ns {
+
+struct Plain {
+    int x;
+};
+
+struct Replaced {
+    int y;
+};
+
+const int kReplaced = 1;
+const int kKept = 2;
+
+class IBase {
+public:
+    virtual void baseMethod() = 0;
+};
+
+class IDerived : public IBase {
+public:
+    virtual void derivedMethod() = 0;
+};
+
+} // namespace ns
+"#;
+
+fn interface_generator() -> Generator {
+    Generator::default()
+        .iid_generator(|name| format!("{name}_IID"))
+        .query_interface_fn("query_interface")
+        .add_ref_fn("add_ref")
+        .release_fn("release")
+}
+
+fn generate(generator: &Generator, source: &str) -> String {
+    let mut output = Vec::new();
+    generator.generate(source, &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+// Compares `output` against the snapshot file with the given name. If the `UPDATE_SNAPSHOTS`
+// environment variable is set, the snapshot file is overwritten instead.
+//
+// Snapshots must be recorded from a real run of the generator, which requires libclang. A missing
+// snapshot is recorded so that it can be reviewed and committed, but the test still fails.
+fn check_snapshot(name: &str, output: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tests")
+        .join(name);
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        write_snapshot(&path, output);
+        return;
+    }
+
+    let Ok(expected) = fs::read_to_string(&path) else {
+        write_snapshot(&path, output);
+        panic!("recorded new snapshot {name}; review it and commit it");
+    };
+
+    // Git may check out the snapshots with CRLF line endings on Windows
+    let expected = expected.replace("\r\n", "\n");
+    assert!(
+        output == expected,
+        "output does not match snapshot {name} (rerun with UPDATE_SNAPSHOTS=1 to update it):\n\
+         {output}"
+    );
+}
+
+fn write_snapshot(path: &Path, output: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, output).unwrap();
+}
+
+#[test]
+fn records() {
+    let generator = Generator::default().default_impls(true);
+    check_snapshot("records.snap", &generate(&generator, RECORDS));
+}
+
+#[test]
+fn enums() {
+    check_snapshot("enums.snap", &generate(&Generator::default(), ENUMS));
+}
+
+#[test]
+fn namespaces() {
+    check_snapshot(
+        "namespaces.snap",
+        &generate(&Generator::default(), NAMESPACES),
+    );
+}
+
+#[test]
+fn interfaces() {
+    check_snapshot(
+        "interfaces.snap",
+        &generate(&interface_generator(), INTERFACES),
+    );
+}
+
+#[test]
+fn catch_panics() {
+    let generator = interface_generator()
+        .catch_panics(true)
+        .panic_value("result", "-1");
+    check_snapshot("catch_panics.snap", &generate(&generator, CALLBACKS));
+}
+
+struct TestEmitter;
+
+impl Emitter for TestEmitter {
+    fn trait_name(&self, interface: &str) -> String {
+        format!("{interface}Methods")
+    }
+
+    fn vtbl_name(&self, interface: &str) -> String {
+        format!("{interface}VirtualTable")
+    }
+
+    fn record_definition(&self, record: &RecordInfo) -> Option<Vec<String>> {
+        if record.name == "Replaced" {
+            Some(vec!["pub struct Replaced;".to_string()])
+        } else {
+            None
+        }
+    }
+
+    fn constant_definition(&self, constant: &Constant, _: &[String]) -> Option<Vec<String>> {
+        if constant.name == "kReplaced" {
+            Some(vec!["pub const kReplaced: i32 = -1;".to_string()])
+        } else {
+            None
+        }
+    }
+
+    fn record_attributes(&self, record: &RecordInfo) -> Vec<String> {
+        if record.is_interface {
+            Vec::new()
+        } else {
+            vec!["#[derive(Debug)]".to_string()]
+        }
+    }
+
+    fn record_items(&self, record: &RecordInfo) -> Vec<String> {
+        vec![format!(
+            "impl {} {{\n    pub const PATH: &'static str = \"{}\";\n}}",
+            record.name,
+            record.module_path.join("::"),
+        )]
+    }
+
+    fn module_items(&self, module_path: &[String]) -> Vec<String> {
+        if module_path.is_empty() {
+            Vec::new()
+        } else {
+            vec![format!(
+                "pub const MODULE: &str = \"{}\";",
+                module_path.join("::")
+            )]
+        }
+    }
+}
+
+#[test]
+fn emitter() {
+    let generator = interface_generator().emitter(TestEmitter);
+    check_snapshot("emitter.snap", &generate(&generator, EMITTER));
+}
+
+#[test]
+fn index() {
+    let mut output = Vec::new();
+    Generator::default()
+        .generate_index(INTERFACES, &mut output, |name| {
+            (name == "IWidget").then(|| "IWidget_IID".to_string())
+        })
+        .unwrap();
+    check_snapshot("index.snap", &String::from_utf8(output).unwrap());
+}
+
+#[test]
+fn parse() {
+    let namespace = Generator::default().parse(INTERFACES).unwrap();
+
+    assert_eq!(namespace.children["base"].records[0].name, "IUnknown");
+
+    let v1 = &namespace.children["api"].children["v1"];
+    let records = v1.records.iter().map(|r| &*r.name).collect::<Vec<_>>();
+    assert_eq!(records, ["IWidget", "IDisposable", "IResettable"]);
+
+    let widget = &v1.records[0];
+    let names = widget
+        .virtual_methods
+        .iter()
+        .map(|m| &*m.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["setValue", "setValue1", "getValue", "getName"]);
+    let cxx_names = widget
+        .virtual_methods
+        .iter()
+        .map(|m| &*m.cxx_name)
+        .collect::<Vec<_>>();
+    assert_eq!(cxx_names, ["setValue", "setValue", "getValue", "getName"]);
+    assert_eq!(widget.bases[0].name.scope, ["base"]);
+    assert_eq!(widget.bases[0].name.name, "IUnknown");
+    assert_eq!(widget.virtual_destructor, None);
+
+    // A virtual destructor which overrides a base class destructor has no entries of its own
+    let disposable = &v1.records[1];
+    assert_eq!(disposable.virtual_destructor, Some(0));
+    let resettable = &v1.records[2];
+    assert_eq!(resettable.virtual_destructor, None);
+    assert_eq!(resettable.bases[0].name.scope, ["api", "v1"]);
+    assert_eq!(resettable.bases[0].bases[0].name.name, "IWidget");
+}
+
+#[test]
+fn overload_names_skip_declared_names() {
+    let source = r#"
+        class IOverloads {
+        public:
+            virtual void foo() = 0;
+            virtual void foo(int value) = 0;
+            virtual void foo1() = 0;
+            virtual void foo(float value) = 0;
+        };
+    "#;
+    let namespace = Generator::default().parse(source).unwrap();
+    let names = namespace.records[0]
+        .virtual_methods
+        .iter()
+        .map(|m| &*m.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["foo", "foo2", "foo1", "foo3"]);
+}
+
+#[test]
+fn platform_dependent_types() {
+    let source = r#"
+        struct Widths {
+            long l;
+            unsigned long ul;
+            char16_t c16;
+            wchar_t wc;
+        };
+    "#;
+    let namespace = Generator::default().parse(source).unwrap();
+    let fields = &namespace.records[0].fields;
+
+    let long_size = mem::size_of::<c_long>();
+    let wchar_size = if cfg!(windows) { 2 } else { 4 };
+    assert!(matches!(fields[0].type_, Type::Signed(size) if size == long_size));
+    assert!(matches!(fields[1].type_, Type::Unsigned(size) if size == long_size));
+    assert!(matches!(fields[2].type_, Type::Unsigned(2)));
+    assert!(matches!(fields[3].type_, Type::Unsigned(size) if size == wchar_size));
+}
+
+#[test]
+fn overriding_methods_are_rejected() {
+    let source = r#"
+        class IBase {
+        public:
+            virtual void method() = 0;
+        };
+
+        class IDerived : public IBase {
+        public:
+            virtual void method() = 0;
+        };
+    "#;
+    let error = Generator::default().parse(source).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("virtual method IDerived::method overrides a base class method"));
+}
+
+#[test]
+fn multiple_inheritance_is_rejected() {
+    let source = r#"
+        class IFirst {
+        public:
+            virtual void first() = 0;
+        };
+
+        class ISecond {
+        public:
+            virtual void second() = 0;
+        };
+
+        class IBoth : public IFirst, public ISecond {
+        public:
+            virtual void both() = 0;
+        };
+    "#;
+    let error = interface_generator()
+        .generate(source, Vec::new())
+        .unwrap_err();
+    assert_eq!(error.to_string(), "type IBoth has more than one base class");
+}
@@ -8,7 +8,7 @@ use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::{ptr, slice};
 
-use vst3::{uid, Class, ComRef, ComWrapper, Steinberg::Vst::*, Steinberg::*};
+use vst3::{uid, Class, ComRef, ComWrapper, ComWrapperStatic, Steinberg::Vst::*, Steinberg::*};
 
 fn copy_cstring(src: &str, dst: &mut [c_char]) {
     let c_string = CString::new(src).unwrap_or_else(|_| CString::default());
@@ -583,10 +583,9 @@ extern "system" fn ModuleExit() -> bool {
     true
 }
 
+static FACTORY: ComWrapperStatic<Factory> = ComWrapperStatic::new(Factory {});
+
 #[no_mangle]
 extern "system" fn GetPluginFactory() -> *mut IPluginFactory {
-    ComWrapper::new(Factory {})
-        .to_com_ptr::<IPluginFactory>()
-        .unwrap()
-        .into_raw()
+    FACTORY.as_com_ref::<IPluginFactory>().unwrap().as_ptr()
 }
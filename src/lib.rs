@@ -11,18 +11,43 @@
 //! where necessary (e.g., definitions which are nested inside a C++ type `SomeType` will be found
 //! inside a `SomeType_` module in the generated bindings).
 //!
+//! Structs and unions other than COM interfaces implement [`Default`], which returns a
+//! zero-initialized value. This is useful for constructing values of types such as
+//! [`ProcessSetup`](Steinberg::Vst::ProcessSetup) or [`ViewRect`](Steinberg::ViewRect) before
+//! passing them to an API which fills them in. Note that the zero-initialized value is not
+//! necessarily the value produced by the type's C++ default constructor: for types whose
+//! constructor in the SDK sets fields to nonzero values, those fields must be set explicitly.
+//!
+//! UTF-16 string types such as [`TChar`](Steinberg::Vst::TChar),
+//! [`String128`](Steinberg::Vst::String128), and [`char16`](Steinberg::char16) are represented
+//! using `u16` on all platforms, regardless of whether the SDK defines them in terms of `char16_t`
+//! or `wchar_t`. This makes it possible to pass them directly to UTF-16 conversion functions such
+//! as [`String::from_utf16`] and [`str::encode_utf16`], or to crates such as `widestring`.
+//!
 //! For each COM interface `IInterface` in the C++ headers, the bindings include a corresponding
 //! Rust type `IInterface`, a virtual table struct `IInterfaceVtbl`, and a trait `IInterfaceTrait`
 //! (excluding `FUnknown`, for which no trait is generated). Each `IInterface` type also implements
 //! the [`Interface`] trait, which holds an associated constant [`Interface::IID`] specifying the
 //! GUID corresponding to that interface.
 //!
+//! When the `presonus` feature is enabled, bindings for the PreSonus plug-in extension interfaces
+//! are generated as well and placed in the `Presonus` module. This requires the `PRESONUS_SDK_DIR`
+//! environment variable to point to the directory containing the PreSonus extension headers.
+//!
 //! # Interacting with COM objects
 //!
 //! The [`ComPtr`] and [`ComRef`] smart pointers are provided for interacting with COM objects.
 //! These types make it safer and more convenient to call methods, cast between interfaces, and
 //! manage reference counts.
 //!
+//! The methods of an interface's base interfaces can be called directly through a smart pointer to
+//! the derived interface, without upcasting first. For instance, since `IAudioProcessor` derives
+//! from `IPluginBase`, the methods of [`IPluginBaseTrait`](Steinberg::IPluginBaseTrait) can be
+//! called on a `ComPtr<IAudioProcessor>`. This is checked statically via the
+//! [`Inherits`](com_scrape_types::Inherits) trait. The methods of `FUnknown` are not exposed this
+//! way; use [`ComPtr::cast`] and [`ComPtr::clone`] instead of calling `queryInterface` and
+//! `addRef` by hand.
+//!
 //! For an overview of how to properly manage ownership and reference counts using [`ComPtr`] and
 //! [`ComRef`], see the [`com-scrape-types` documentation](com_scrape_types#reference-counting).
 //!
@@ -56,5 +81,94 @@
 //!
 //! For more detail on implementing COM interfaces from rust, see the
 //! [`com-scrape-types` documentation](com_scrape_types#implementing-com-interfaces-from-rust).
+//!
+//! The [`TResult`] newtype makes it possible to write method implementations in terms of
+//! `Result<(), TResult>`, propagating failures with the `?` operator, and to convert the result
+//! back to a `tresult` at the interface boundary. The [`tresult_try`] macro performs this
+//! conversion for a block of code, and can also write a successful result to an out-parameter.
+//!
+//! A panic in an interface method implementation is caught before it can unwind into the host,
+//! and the method returns `kInternalError` (or a zero value, for methods which do not return a
+//! `tresult`). A function to be notified of such panics, e.g. for logging, can be installed with
+//! [`set_panic_handler`](com_scrape_types::set_panic_handler).
+//!
+//! Interfaces which are not declared in the VST 3 SDK headers (such as vendor-specific extension
+//! interfaces) can be defined by hand in downstream crates and used with [`ComPtr`], [`ComRef`],
+//! and [`ComWrapper`] just like the generated ones. Each generated interface type provides a
+//! `make_vtbl` function which can be used to fill in the base portion of a derived interface's
+//! virtual table. See the
+//! [`com-scrape-types` documentation](com_scrape_types#defining-interfaces-outside-of-generated-bindings)
+//! for the full set of definitions required. Alternatively, these definitions can be generated
+//! from a Rust trait definition using the `com_interface` attribute macro (available with the
+//! `macros` feature enabled), passing `crate = vst3::com_scrape_types`:
+//!
+//! ```ignore
+//! #[com_interface(
+//!     iid = EXTENSION_IID,
+//!     bases(FUnknown),
+//!     crate = vst3::com_scrape_types
+//! )]
+//! pub trait IExtension {
+//!     unsafe fn extension_method(&self) -> tresult;
+//! }
+//! ```
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(test)]
+mod tests;
+
+/// Evaluates a block which returns a `Result`, converting the result to a `tresult`.
+///
+/// Within the block, the `?` operator can be used to return early with a [`TResult`] error code.
+/// `return` returns from the block rather than from the enclosing function.
+///
+/// In the first form, the block evaluates to `Result<(), TResult>`, and `Ok(())` is converted to
+/// `kResultOk`:
+///
+/// ```ignore
+/// unsafe fn setActive(&self, state: TBool) -> tresult {
+///     tresult_try!({
+///         self.set_active(state != 0)?;
+///         Ok(())
+///     })
+/// }
+/// ```
+///
+/// In the second form, the block evaluates to `Result<T, TResult>`, and the value in `Ok` is
+/// written to the out-parameter `out: *mut T`. The previous contents of `*out` are not dropped, so
+/// `out` may point to uninitialized memory. If `out` is null, the block is not evaluated and
+/// `kInvalidArgument` is returned:
+///
+/// ```ignore
+/// unsafe fn getSize(&self, size: *mut ViewRect) -> tresult {
+///     tresult_try!(size => {
+///         let (width, height) = self.size().ok_or(TResult::NOT_INITIALIZED)?;
+///         Ok(ViewRect { left: 0, top: 0, right: width, bottom: height })
+///     })
+/// }
+/// ```
+#[macro_export]
+macro_rules! tresult_try {
+    ($out:expr => $body:block) => {{
+        let out = $out;
+        if out.is_null() {
+            $crate::Steinberg::kInvalidArgument
+        } else {
+            #[allow(clippy::redundant_closure_call)]
+            let result = (|| -> ::core::result::Result<_, $crate::TResult> { $body })();
+            match result {
+                ::core::result::Result::Ok(value) => {
+                    ::core::ptr::write(out, value);
+                    $crate::Steinberg::kResultOk
+                }
+                ::core::result::Result::Err(result) => result.0,
+            }
+        }
+    }};
+    ($body:block) => {{
+        #[allow(clippy::redundant_closure_call)]
+        let result = (|| -> ::core::result::Result<(), $crate::TResult> { $body })();
+        $crate::TResult::from(result).0
+    }};
+}
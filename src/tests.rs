@@ -0,0 +1,50 @@
+use std::cell::Cell;
+use std::hint;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use crate::Steinberg::{kInvalidArgument, kResultOk, tresult};
+use crate::TResult;
+
+#[test]
+fn tresult_try() {
+    let result: tresult = crate::tresult_try!({ Ok(()) });
+    assert_eq!(result, kResultOk);
+
+    let result: tresult = crate::tresult_try!({
+        TResult::NOT_IMPLEMENTED.into_result()?;
+        Ok(())
+    });
+    assert_eq!(result, TResult::NOT_IMPLEMENTED.0);
+}
+
+#[test]
+fn tresult_try_out() {
+    let mut out = MaybeUninit::<String>::uninit();
+    let result = unsafe { crate::tresult_try!(out.as_mut_ptr() => { Ok(String::from("value")) }) };
+    assert_eq!(result, kResultOk);
+    assert_eq!(unsafe { out.assume_init() }, "value");
+
+    let mut out = String::from("previous");
+    let result = unsafe {
+        crate::tresult_try!(&mut out as *mut String => {
+            TResult::INTERNAL_ERROR.into_result()?;
+            Ok(String::from("value"))
+        })
+    };
+    assert_eq!(result, TResult::INTERNAL_ERROR.0);
+    assert_eq!(out, "previous");
+
+    // Passed through black_box, since writing through a literal null pointer is rejected by a lint
+    // even though the write is never reached.
+    let null = hint::black_box(ptr::null_mut::<String>());
+    let evaluated = Cell::new(false);
+    let result = unsafe {
+        crate::tresult_try!(null => {
+            evaluated.set(true);
+            Ok(String::from("value"))
+        })
+    };
+    assert_eq!(result, kInvalidArgument);
+    assert!(!evaluated.get());
+}
@@ -0,0 +1,29 @@
+//! Prints a Markdown index of the interfaces in the VST 3 API.
+//!
+//! Usage: `cargo run --example interface_index -- <path to VST 3 SDK> [extension headers...]`
+
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+fn main() {
+    let mut args = env::args_os().skip(1);
+
+    let sdk_dir = if let Some(dir) = args.next() {
+        dir
+    } else {
+        eprintln!("usage: interface_index <path to VST 3 SDK> [extension headers...]");
+        process::exit(1);
+    };
+
+    let extension_headers = args.map(PathBuf::from).collect::<Vec<_>>();
+
+    let stdout = io::stdout();
+    if let Err(err) =
+        vst3_bindgen::generate_index(Path::new(&sdk_dir), &extension_headers, stdout.lock())
+    {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -1,10 +1,13 @@
 //! A binding generator for the VST 3 API. `vst3-bindgen` can be used to generate Rust bindings for
 //! the VST 3 API from the original C++ headers.
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fmt::{self, Display};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::{fs, io};
 
 fn find_headers<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, io::Error> {
@@ -45,8 +48,135 @@ fn parse_iid(tokens: &[String]) -> Option<String> {
     None
 }
 
-/// Generates Rust bindings given a path to the VST 3 SDK.
-pub fn generate(sdk_dir: &Path, mut sink: impl Write) -> Result<(), Box<dyn Error>> {
+fn parse_u32(token: &str) -> Option<u32> {
+    if let Some(hex) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        token.parse().ok()
+    }
+}
+
+// Formats the IID declared by a DECLARE_CLASS_IID invocation in registry format, i.e.
+// {XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}.
+fn format_iid(tokens: &[String]) -> Option<String> {
+    let l1 = parse_u32(tokens.get(4)?)?;
+    let l2 = parse_u32(tokens.get(6)?)?;
+    let l3 = parse_u32(tokens.get(8)?)?;
+    let l4 = parse_u32(tokens.get(10)?)?;
+
+    Some(format!(
+        "{{{:08X}-{:04X}-{:04X}-{:04X}-{:04X}{:08X}}}",
+        l1,
+        l2 >> 16,
+        l2 & 0xFFFF,
+        l3 >> 16,
+        l3 & 0xFFFF,
+        l4
+    ))
+}
+
+/// The version of a copy of the VST 3 SDK, as declared in `pluginterfaces/vst/vsttypes.h`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SdkVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub sub: u32,
+}
+
+impl Display for SdkVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.sub)
+    }
+}
+
+/// The earliest version of the VST 3 SDK supported by [`check_sdk`].
+///
+/// Versions from this one up to (but not including) the next major version are supported. The
+/// generator configuration (the skipped headers and types, and the result codes used by the support
+/// code) is maintained against the 3.7 series of the interface headers.
+pub const MIN_SDK_VERSION: SdkVersion = SdkVersion {
+    major: 3,
+    minor: 7,
+    sub: 0,
+};
+
+// Headers which must be present in any copy of the SDK that bindings can be generated from.
+const REQUIRED_HEADERS: &[&str] = &[
+    "pluginterfaces/base/funknown.h",
+    "pluginterfaces/base/ipluginbase.h",
+    "pluginterfaces/vst/vsttypes.h",
+];
+
+/// Checks that `sdk_dir` contains a copy of the VST 3 SDK which bindings can be generated from,
+/// and returns its version.
+///
+/// Returns an error describing the problem if `sdk_dir` does not exist, if any of the headers
+/// required for generating bindings are missing (e.g. if `sdk_dir` points to the wrong directory,
+/// or if the SDK was cloned without `--recursive`), or if the SDK version is not supported (see
+/// [`MIN_SDK_VERSION`]).
+pub fn check_sdk(sdk_dir: &Path) -> Result<SdkVersion, Box<dyn Error>> {
+    if !sdk_dir.is_dir() {
+        return Err(format!("VST 3 SDK directory {} does not exist", sdk_dir.display()).into());
+    }
+
+    for header in REQUIRED_HEADERS {
+        if !sdk_dir.join(header).is_file() {
+            return Err(format!(
+                "{} does not contain the VST 3 SDK interface headers: {header} is missing (the SDK \
+                 directory should be the one containing pluginterfaces/, and if the SDK was cloned \
+                 with git, the pluginterfaces submodule must be checked out)",
+                sdk_dir.display()
+            )
+            .into());
+        }
+    }
+
+    let vsttypes_path = sdk_dir.join("pluginterfaces/vst/vsttypes.h");
+    let vsttypes = fs::read_to_string(&vsttypes_path)?;
+
+    let define = |name: &str| -> Option<u32> {
+        vsttypes.lines().find_map(|line| {
+            let mut tokens = line.split_whitespace();
+            if tokens.next()? == "#define" && tokens.next()? == name {
+                tokens.next()?.parse().ok()
+            } else {
+                None
+            }
+        })
+    };
+
+    let (Some(major), Some(minor)) = (define("kVstVersionMajor"), define("kVstVersionMinor"))
+    else {
+        return Err(format!(
+            "couldn't determine the VST 3 SDK version from {}",
+            vsttypes_path.display()
+        )
+        .into());
+    };
+    let version = SdkVersion {
+        major,
+        minor,
+        sub: define("kVstVersionSub").unwrap_or(0),
+    };
+
+    if version < MIN_SDK_VERSION || version.major != MIN_SDK_VERSION.major {
+        return Err(format!(
+            "unsupported VST 3 SDK version {version} in {} (supported versions are {} up to but \
+             not including {}.0.0)",
+            sdk_dir.display(),
+            MIN_SDK_VERSION,
+            MIN_SDK_VERSION.major + 1,
+        )
+        .into());
+    }
+
+    Ok(version)
+}
+
+fn collect_source(sdk_dir: &Path, extension_headers: &[PathBuf]) -> Result<String, Box<dyn Error>> {
     let pluginterfaces_path = sdk_dir.join("pluginterfaces");
     let headers = find_headers(&pluginterfaces_path)?;
 
@@ -70,11 +200,19 @@ pub fn generate(sdk_dir: &Path, mut sink: impl Write) -> Result<(), Box<dyn Erro
         writeln!(source, "#include \"{}\"", name)?;
     }
 
-    writeln!(sink, "mod __bindings {{")?;
-    writeln!(sink)?;
+    for header in extension_headers {
+        let name = header
+            .to_str()
+            .ok_or("extension header path is not valid UTF-8")?;
 
-    writeln!(sink, "{}", include_str!("support.rs"))?;
+        use std::fmt::Write;
+        writeln!(source, "#include \"{}\"", name)?;
+    }
+
+    Ok(source)
+}
 
+fn generator(sdk_dir: &Path) -> com_scrape::Generator {
     com_scrape::Generator::default()
         .skip_types(&[
             "Adopt",
@@ -84,13 +222,43 @@ pub fn generate(sdk_dir: &Path, mut sink: impl Write) -> Result<(), Box<dyn Erro
             "LARGE_INT",
         ])
         .skip_interface_trait("FUnknown")
+        .default_impls(true)
+        .catch_panics(true)
+        .panic_value("tresult", "crate::__bindings::Steinberg::kInternalError")
         .constant_parser(parse_iid)
         .iid_generator(|name| format!("crate::__bindings::tuid_as_guid({name}_iid)"))
         .query_interface_fn("crate::__bindings::FUnknown_query_interface")
         .add_ref_fn("crate::__bindings::FUnknown_add_ref")
         .release_fn("crate::__bindings::FUnknown_release")
         .include_path(&sdk_dir)
-        .generate(source, &mut sink)?;
+}
+
+/// Generates Rust bindings given a path to the VST 3 SDK.
+pub fn generate(sdk_dir: &Path, sink: impl Write) -> Result<(), Box<dyn Error>> {
+    generate_with_extensions(sdk_dir, &[], sink)
+}
+
+/// Generates Rust bindings given a path to the VST 3 SDK and a list of additional headers which
+/// declare third-party extension interfaces (e.g. the PreSonus plug-in extensions).
+///
+/// Extension headers are parsed along with the SDK headers, so they may include SDK headers
+/// relative to `sdk_dir`. Definitions from extension headers are placed in modules corresponding
+/// to their own namespaces (e.g. `Presonus`) alongside the `Steinberg` module.
+pub fn generate_with_extensions(
+    sdk_dir: &Path,
+    extension_headers: &[PathBuf],
+    mut sink: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    check_sdk(sdk_dir)?;
+
+    let source = collect_source(sdk_dir, extension_headers)?;
+
+    writeln!(sink, "mod __bindings {{")?;
+    writeln!(sink)?;
+
+    writeln!(sink, "{}", include_str!("support.rs"))?;
+
+    generator(sdk_dir).generate(source, &mut sink)?;
 
     writeln!(sink)?;
     writeln!(sink, "}}")?;
@@ -98,3 +266,38 @@ pub fn generate(sdk_dir: &Path, mut sink: impl Write) -> Result<(), Box<dyn Erro
 
     Ok(())
 }
+
+/// Generates a Markdown index of the interfaces in the VST 3 API, given a path to the VST 3 SDK
+/// and an optional list of extension headers (see [`generate_with_extensions`]).
+///
+/// The index lists each interface along with its IID, the module in which its bindings are
+/// located, its base and derived interfaces, and its methods.
+pub fn generate_index(
+    sdk_dir: &Path,
+    extension_headers: &[PathBuf],
+    sink: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    check_sdk(sdk_dir)?;
+
+    let source = collect_source(sdk_dir, extension_headers)?;
+
+    let iids = Rc::new(RefCell::new(HashMap::new()));
+    let iids_inner = iids.clone();
+
+    generator(sdk_dir)
+        .constant_parser(move |tokens| {
+            if tokens
+                .first()
+                .is_some_and(|first| first == "DECLARE_CLASS_IID")
+            {
+                if let (Some(name), Some(iid)) = (tokens.get(2), format_iid(tokens)) {
+                    iids_inner.borrow_mut().insert(name.clone(), iid);
+                }
+            }
+
+            None
+        })
+        .generate_index(source, sink, |name| iids.borrow().get(name).cloned())?;
+
+    Ok(())
+}
@@ -4,12 +4,23 @@
 
 use std::ffi::c_void;
 
-use Steinberg::{int8, kNoInterface, kResultOk, tresult, uint32, FUnknown, FUnknownVtbl, TUID};
+use Steinberg::{
+    int8, kInternalError, kInvalidArgument, kNoInterface, kNotImplemented, kNotInitialized,
+    kOutOfMemory, kResultFalse, kResultOk, tresult, uint32, FUnknown, FUnknownVtbl, TUID,
+};
 
-use com_scrape_types::{Construct, Guid, Header, InterfaceList, Wrapper};
+use com_scrape_types::{
+    trace_add_ref, trace_query_interface, trace_release, Construct, Guid, Header, InterfaceList,
+    ResultCodes, Wrapper,
+};
 
 pub use com_scrape_types;
-pub use com_scrape_types::{Class, ComPtr, ComRef, ComWrapper, Interface};
+#[cfg(feature = "macros")]
+pub use com_scrape_types::com_interface;
+pub use com_scrape_types::{
+    AtomicComPtr, Class, ComPtr, ComRef, ComResult, ComWeak, ComWrapper, ComWrapperLocal,
+    ComWrapperStatic, IdentityKey, Interface, InterfaceMap, OnceComPtr, OutPtr,
+};
 
 const fn tuid_as_guid(tuid: TUID) -> Guid {
     [
@@ -58,7 +69,7 @@ unsafe fn FUnknown_release(this: *mut c_void) -> usize {
 }
 
 impl FUnknown {
-    const fn make_vtbl<C, W, const OFFSET: isize>() -> FUnknownVtbl
+    pub const fn make_vtbl<C, W, const OFFSET: isize>() -> FUnknownVtbl
     where
         C: Class,
         W: Wrapper<C>,
@@ -73,12 +84,24 @@ impl FUnknown {
             W: Wrapper<C>,
         {
             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
-            if let Some(result) = C::Interfaces::query(&*(_iid as *const Guid)) {
-                let ptr = W::data_from_header(header_ptr);
+            let ptr = W::data_from_header(header_ptr);
+            let iid = &*(_iid as *const Guid);
+
+            let result = if let Some(wrapper_ptr) = W::query_wrapper(ptr, iid) {
+                Some(wrapper_ptr)
+            } else if let Some(result) = W::query_outer(ptr, iid) {
+                result
+            } else if let Some(offset) = C::Interfaces::query(iid) {
                 W::add_ref(ptr);
+                Some((header_ptr as *mut u8).offset(offset) as *mut c_void)
+            } else {
+                None
+            };
 
-                *obj = (header_ptr as *mut u8).offset(result) as *mut c_void;
+            trace_query_interface::<C>(this as *const c_void, iid, result.is_some());
 
+            if let Some(result) = result {
+                *obj = result;
                 kResultOk
             } else {
                 kNoInterface
@@ -92,7 +115,9 @@ impl FUnknown {
         {
             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
             let ptr = W::data_from_header(header_ptr);
-            W::add_ref(ptr) as uint32
+            let count = W::add_ref(ptr);
+            trace_add_ref::<C>(this as *const c_void, count);
+            count as uint32
         }
 
         unsafe extern "system" fn release<C, W, const OFFSET: isize>(this: *mut FUnknown) -> uint32
@@ -102,7 +127,9 @@ impl FUnknown {
         {
             let header_ptr = (this as *mut u8).offset(-OFFSET) as *mut Header<C>;
             let ptr = W::data_from_header(header_ptr);
-            W::release(ptr) as uint32
+            let count = W::release(ptr);
+            trace_release::<C>(this as *const c_void, count);
+            count as uint32
         }
 
         FUnknownVtbl {
@@ -174,3 +201,46 @@ const fn uid_impl(a: u32, b: u32, c: u32, d: u32) -> TUID {
         ((d & 0x000000FF) >> 0) as int8,
     ]
 }
+
+/// The [`tresult`] codes defined by the VST 3 SDK, for use with [`ComResult`].
+///
+/// On Windows, these are the standard `HRESULT` values; on other platforms, the SDK defines its own
+/// values.
+pub struct TResultCodes;
+
+impl ResultCodes for TResultCodes {
+    type Code = tresult;
+
+    const OK: tresult = kResultOk;
+    const FALSE: tresult = kResultFalse;
+    const NO_INTERFACE: tresult = kNoInterface;
+    const INVALID_ARGUMENT: tresult = kInvalidArgument;
+    const NOT_IMPLEMENTED: tresult = kNotImplemented;
+    const INTERNAL_ERROR: tresult = kInternalError;
+    const NOT_INITIALIZED: tresult = kNotInitialized;
+    const OUT_OF_MEMORY: tresult = kOutOfMemory;
+}
+
+/// A [`tresult`] value.
+///
+/// Methods in the generated bindings return raw `tresult` codes. `TResult` makes it possible to
+/// write the body of an interface method in terms of `Result<(), TResult>`, using the `?`
+/// operator to propagate failures, and then convert the result back to a `tresult` at the
+/// boundary:
+///
+/// ```ignore
+/// unsafe fn setActive(&self, state: TBool) -> tresult {
+///     TResult::from(self.set_active(state != 0)).0
+/// }
+///
+/// fn set_active(&self, active: bool) -> Result<(), TResult> {
+///     TResult::new(self.host.notify(active)).into_result()?;
+///     self.allocate_buffers().ok_or(TResult::OUT_OF_MEMORY)?;
+///     Ok(())
+/// }
+/// ```
+///
+/// Since `kResultTrue` and `kResultOk` share a value, `kResultFalse` is treated as a failure by
+/// [`into_result`](ComResult::into_result). Methods which return `kResultFalse` as a non-error
+/// result should compare against [`TResult::FALSE`] directly.
+pub type TResult = ComResult<TResultCodes>;